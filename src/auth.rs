@@ -0,0 +1,223 @@
+use std::{fmt::Write, path::PathBuf, sync::Arc};
+
+use rocket::{
+    Request,
+    figment::{
+        Figment,
+        providers::{Format, Toml},
+    },
+    http::Status,
+    request::{FromRequest, Outcome},
+};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use crate::metrics::Metrics;
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum Role {
+    // passes every role check
+    Admin,
+    Moderator,
+    ReadonlyMetrics,
+}
+
+// read from the `auth` table of Rocket.toml, or `ROCKET_AUTH`
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct AuthConfig {
+    pub keys_file: PathBuf,
+    // serve `/api/metrics` without a key
+    pub public_metrics: bool,
+}
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        AuthConfig {
+            keys_file: PathBuf::from("./api_keys.toml"),
+            public_metrics: true,
+        }
+    }
+}
+
+impl AuthConfig {
+    pub fn from_figment(figment: &Figment) -> Result<Self, String> {
+        figment
+            .focus("auth")
+            .extract::<AuthConfig>()
+            .map_err(|e| e.to_string())
+    }
+}
+
+// one `[[keys]]` entry of the keys file, only the key's hash is stored
+#[derive(Deserialize, Clone)]
+struct ApiKey {
+    name: String,
+    sha256: String,
+    roles: Vec<Role>,
+}
+
+impl ApiKey {
+    fn has_role(&self, role: Role) -> bool {
+        self.roles
+            .iter()
+            .any(|granted| *granted == Role::Admin || *granted == role)
+    }
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct KeysFile {
+    keys: Vec<ApiKey>,
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+    Sha256::digest(data)
+        .iter()
+        .fold(String::new(), |mut out, byte| {
+            let _ = write!(out, "{:02x}", byte);
+            out
+        })
+}
+
+// compares every byte so the time taken doesn't leak how much matched
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+pub struct Auth {
+    keys: Vec<ApiKey>,
+    public_metrics: bool,
+    metrics: Arc<Metrics>,
+}
+
+impl Auth {
+    // a missing keys file means no keys, and every guarded route stays locked
+    pub fn load(config: &AuthConfig, metrics: Arc<Metrics>) -> Result<Self, String> {
+        if !config.keys_file.exists() {
+            log::warn!(
+                "No API keys file at {}, privileged routes are locked",
+                config.keys_file.display()
+            );
+        }
+
+        let keys_file = Figment::from(Toml::file(&config.keys_file))
+            .extract::<KeysFile>()
+            .map_err(|e| e.to_string())?;
+
+        let mut keys = keys_file.keys;
+        for key in keys.iter_mut() {
+            key.sha256.make_ascii_lowercase();
+        }
+
+        Ok(Auth {
+            keys,
+            public_metrics: config.public_metrics,
+            metrics,
+        })
+    }
+
+    // every key is checked, so timing doesn't tell which one came close
+    fn find(&self, token: &str) -> Option<&ApiKey> {
+        let hash = sha256_hex(token.as_bytes());
+        self.keys.iter().fold(None, |found, key| {
+            let matches = constant_time_eq(hash.as_bytes(), key.sha256.as_bytes());
+            found.or(matches.then_some(key))
+        })
+    }
+
+    fn reject(&self, request: &Request<'_>, role: Role, status: Status, reason: &str) -> Status {
+        let ip = request
+            .client_ip()
+            .map(|ip| ip.to_string())
+            .unwrap_or_else(|| "unknown".to_string());
+        log::warn!(
+            "Rejected {:?} request for {} {} from {}: {}",
+            role,
+            request.method(),
+            request.uri(),
+            ip,
+            reason
+        );
+        self.metrics.increment("auth_failures_total");
+        status
+    }
+
+    fn authorize(&self, request: &Request<'_>, role: Role) -> Result<String, Status> {
+        let Some(header) = request.headers().get_one("Authorization") else {
+            return Err(self.reject(request, role, Status::Unauthorized, "no credentials"));
+        };
+        let Some(token) = header.strip_prefix("Bearer ") else {
+            return Err(self.reject(request, role, Status::Unauthorized, "not a bearer token"));
+        };
+        let Some(key) = self.find(token.trim()) else {
+            return Err(self.reject(request, role, Status::Unauthorized, "unknown key"));
+        };
+        if !key.has_role(role) {
+            let reason = format!("key {} lacks the role", key.name);
+            return Err(self.reject(request, role, Status::Forbidden, &reason));
+        }
+
+        Ok(key.name.clone())
+    }
+}
+
+fn authorize(request: &Request<'_>, role: Role) -> Outcome<String, ()> {
+    let Some(auth) = request.rocket().state::<Arc<Auth>>() else {
+        return Outcome::Error((Status::InternalServerError, ()));
+    };
+
+    match auth.authorize(request, role) {
+        Ok(name) => Outcome::Success(name),
+        Err(status) => Outcome::Error((status, ())),
+    }
+}
+
+// a request made with a key holding the `admin` role
+pub struct Admin {
+    pub key: String,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Admin {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        authorize(request, Role::Admin).map(|key| Admin { key })
+    }
+}
+
+// a request made with a key holding the `moderator` role, or `admin`
+pub struct Moderator {
+    pub key: String,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Moderator {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        authorize(request, Role::Moderator).map(|key| Moderator { key })
+    }
+}
+
+// a request allowed to read metrics, with or without a key
+pub struct MetricsReader;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for MetricsReader {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let public = request
+            .rocket()
+            .state::<Arc<Auth>>()
+            .is_some_and(|auth| auth.public_metrics);
+        if public {
+            return Outcome::Success(MetricsReader);
+        }
+
+        authorize(request, Role::ReadonlyMetrics).map(|_| MetricsReader)
+    }
+}
@@ -46,6 +46,16 @@ impl<K: Eq + Hash, V: Clone> Cache<K, V> {
         map.insert(key, cache_entry);
     }
 
+    pub fn remove(&self, key: &K) -> bool {
+        let mut map = self.map.lock().expect("Lock cache data");
+        map.remove(key).is_some()
+    }
+
+    pub fn clear(&self) {
+        let mut map = self.map.lock().expect("Lock cache data");
+        map.clear();
+    }
+
     // TODO: clean up method if/when it's relevant
 }
 
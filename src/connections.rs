@@ -0,0 +1,152 @@
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::{Arc, Mutex},
+};
+
+use rocket::{
+    Request,
+    figment::Figment,
+    http::Status,
+    request::{FromRequest, Outcome},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::metrics::Metrics;
+
+// read from the `connections` table of Rocket.toml, or `ROCKET_CONNECTIONS`
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct ConnectionLimits {
+    pub max_total: usize,
+    pub max_per_ip: usize,
+}
+
+impl Default for ConnectionLimits {
+    fn default() -> Self {
+        ConnectionLimits {
+            max_total: 1024,
+            max_per_ip: 16,
+        }
+    }
+}
+
+impl ConnectionLimits {
+    pub fn from_figment(figment: &Figment) -> Result<Self, String> {
+        figment
+            .focus("connections")
+            .extract::<ConnectionLimits>()
+            .map_err(|e| e.to_string())
+    }
+}
+
+#[derive(Serialize)]
+pub struct ConnectionsHealth {
+    open: usize,
+    clients: usize,
+    max_total: usize,
+    max_per_ip: usize,
+}
+
+#[derive(Default)]
+struct Occupancy {
+    total: usize,
+    per_ip: HashMap<IpAddr, usize>,
+}
+
+// tracks open WebSocket and live image connections against `ConnectionLimits`
+pub struct ConnectionLimiter {
+    limits: ConnectionLimits,
+    occupancy: Mutex<Occupancy>,
+    metrics: Arc<Metrics>,
+}
+
+impl ConnectionLimiter {
+    pub fn new(limits: ConnectionLimits, metrics: Arc<Metrics>) -> Self {
+        ConnectionLimiter {
+            limits,
+            occupancy: Mutex::new(Occupancy::default()),
+            metrics,
+        }
+    }
+
+    fn acquire(&self, ip: Option<IpAddr>) -> Result<(), Status> {
+        let mut occupancy = self.occupancy.lock().expect("Lock connection occupancy");
+        if occupancy.total >= self.limits.max_total {
+            self.metrics.increment("ws_connections_rejected_total");
+            return Err(Status::ServiceUnavailable);
+        }
+
+        // rejected clients never get an entry, so they can't pile up
+        if let Some(ip) = ip {
+            let count = occupancy.per_ip.get(&ip).copied().unwrap_or_default();
+            if count >= self.limits.max_per_ip {
+                self.metrics.increment("ws_connections_rejected_total");
+                return Err(Status::TooManyRequests);
+            }
+            occupancy.per_ip.insert(ip, count + 1);
+        }
+
+        occupancy.total += 1;
+        self.metrics
+            .set_gauge("ws_connections_open", occupancy.total as i64);
+        Ok(())
+    }
+
+    fn release(&self, ip: Option<IpAddr>) {
+        let mut occupancy = self.occupancy.lock().expect("Lock connection occupancy");
+        if let Some(ip) = ip {
+            let count = occupancy.per_ip.entry(ip).or_default();
+            *count = count.saturating_sub(1);
+            if *count == 0 {
+                occupancy.per_ip.remove(&ip);
+            }
+        }
+
+        occupancy.total = occupancy.total.saturating_sub(1);
+        self.metrics
+            .set_gauge("ws_connections_open", occupancy.total as i64);
+    }
+
+    pub fn health(&self) -> ConnectionsHealth {
+        let occupancy = self.occupancy.lock().expect("Lock connection occupancy");
+        ConnectionsHealth {
+            open: occupancy.total,
+            clients: occupancy.per_ip.len(),
+            max_total: self.limits.max_total,
+            max_per_ip: self.limits.max_per_ip,
+        }
+    }
+}
+
+// a slot for one connection, given back when dropped
+pub struct ConnectionPermit {
+    limiter: Arc<ConnectionLimiter>,
+    ip: Option<IpAddr>,
+}
+
+impl Drop for ConnectionPermit {
+    fn drop(&mut self) {
+        self.limiter.release(self.ip);
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for ConnectionPermit {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let Some(limiter) = request.rocket().state::<Arc<ConnectionLimiter>>() else {
+            return Outcome::Error((Status::InternalServerError, ()));
+        };
+
+        let ip = request.client_ip();
+        match limiter.acquire(ip) {
+            Ok(()) => Outcome::Success(ConnectionPermit {
+                limiter: limiter.clone(),
+                ip,
+            }),
+            Err(status) => Outcome::Error((status, ())),
+        }
+    }
+}
@@ -0,0 +1,31 @@
+use std::sync::Arc;
+
+use rocket::{State, get, serde::json::Json};
+use serde::Serialize;
+
+use crate::{
+    connections::{ConnectionLimiter, ConnectionsHealth},
+    kennel::{KennelHealth, Loader},
+};
+
+#[derive(Serialize)]
+pub struct Health {
+    status: &'static str,
+    kennel: KennelHealth,
+    connections: ConnectionsHealth,
+}
+
+#[get("/health")]
+pub async fn health_handler(
+    kennel: &State<Arc<Loader>>,
+    connections: &State<Arc<ConnectionLimiter>>,
+) -> Json<Health> {
+    let kennel = kennel.health().await;
+    let status = if kennel.is_ok() { "ok" } else { "degraded" };
+
+    Json(Health {
+        status,
+        kennel,
+        connections: connections.health(),
+    })
+}
@@ -0,0 +1,127 @@
+use std::{sync::Arc, time::Duration};
+
+use rocket::{Route, State as RocketState, delete, get, http, post, put, routes};
+
+use crate::{
+    auth::{Admin, Moderator},
+    cache::Cache,
+    kennel::{
+        loader::{Available, Loader},
+        response::Response,
+    },
+};
+
+static MIN_TICK_INTERVAL_MS: u64 = 50;
+static MAX_TICK_INTERVAL_MS: u64 = 60 * 60 * 1000;
+static MAX_STEPS: u64 = 1000;
+
+// moderators can look at and hold the clock, everything else needs an admin
+#[get("/")]
+async fn status_handler(_moderator: Moderator, kennel: Available) -> Response {
+    Response::new_json(kennel.status().await)
+}
+
+#[post("/pause")]
+async fn pause_handler(moderator: Moderator, kennel: Available) -> Response {
+    log::info!("{} paused the kennel", moderator.key);
+    kennel.pause().await;
+    Response::new_json(kennel.status().await)
+}
+
+#[post("/resume")]
+async fn resume_handler(moderator: Moderator, kennel: Available) -> Response {
+    log::info!("{} resumed the kennel", moderator.key);
+    kennel.resume().await;
+    Response::new_json(kennel.status().await)
+}
+
+#[post("/step?<count>")]
+async fn step_handler(moderator: Moderator, count: Option<u64>, kennel: Available) -> Response {
+    let count = count.unwrap_or(1);
+    if !(1..=MAX_STEPS).contains(&count) {
+        let message = format!("count must be between 1 and {}", MAX_STEPS);
+        return Response::new_err(http::Status::BadRequest, &message);
+    }
+
+    log::info!("{} stepped the kennel {} ticks", moderator.key, count);
+    match kennel.step(count).await {
+        Ok(()) => Response::new_json(kennel.status().await),
+        Err(message) => Response::new_err(http::Status::Conflict, &message),
+    }
+}
+
+#[put("/interval?<ms>")]
+async fn interval_handler(admin: Admin, ms: u64, kennel: Available) -> Response {
+    if !(MIN_TICK_INTERVAL_MS..=MAX_TICK_INTERVAL_MS).contains(&ms) {
+        let message = format!(
+            "ms must be between {} and {}",
+            MIN_TICK_INTERVAL_MS, MAX_TICK_INTERVAL_MS
+        );
+        return Response::new_err(http::Status::BadRequest, &message);
+    }
+
+    log::info!("{} set the tick interval to {}ms", admin.key, ms);
+    kennel.set_tick_interval(Duration::from_millis(ms)).await;
+    Response::new_json(kennel.status().await)
+}
+
+// without a seed, picks a random one and reports it so the run can be repeated
+#[post("/reseed?<seed>")]
+async fn reseed_handler(admin: Admin, seed: Option<u64>, kennel: Available) -> Response {
+    let seed = seed.unwrap_or_else(rand::random);
+    log::info!("{} reseeded the kennel with {}", admin.key, seed);
+    kennel.reseed(seed).await;
+    Response::new_json(serde_json::json!({ "seed": seed }))
+}
+
+#[post("/reload")]
+async fn reload_handler(admin: Admin, kennel: &RocketState<Arc<Loader>>) -> Response {
+    log::info!("{} reloaded the kennel", admin.key);
+    match kennel.reload().await {
+        Ok(reload) => Response::new_json(reload),
+        Err(message) => Response::new_err(http::Status::UnprocessableEntity, &message),
+    }
+}
+
+#[delete("/render")]
+async fn clear_render_handler(admin: Admin, kennel: Available) -> Response {
+    log::info!("{} cleared the render cache", admin.key);
+    kennel.clear_render_cache().await;
+    Response::new_json(serde_json::json!({ "cleared": true }))
+}
+
+#[delete("/cache")]
+fn clear_cache_handler(admin: Admin, cache: &RocketState<Cache<String, String>>) -> Response {
+    log::info!("{} cleared the cache", admin.key);
+    cache.clear();
+    Response::new_json(serde_json::json!({ "cleared": true }))
+}
+
+#[delete("/cache/<key>")]
+fn invalidate_cache_handler(
+    admin: Admin,
+    key: String,
+    cache: &RocketState<Cache<String, String>>,
+) -> Response {
+    log::info!("{} invalidated cache key {}", admin.key, key);
+    if cache.remove(&key) {
+        Response::new_json(serde_json::json!({ "invalidated": key }))
+    } else {
+        Response::new_err(http::Status::NotFound, &format!("{} not found", key))
+    }
+}
+
+pub fn admin_routes() -> Vec<Route> {
+    routes![
+        status_handler,
+        pause_handler,
+        resume_handler,
+        step_handler,
+        interval_handler,
+        reseed_handler,
+        reload_handler,
+        clear_render_handler,
+        clear_cache_handler,
+        invalidate_cache_handler,
+    ]
+}
@@ -0,0 +1,270 @@
+use std::{collections::VecDeque, io::Cursor, time::Duration};
+
+use image::{
+    Delay, Frame, RgbaImage,
+    codecs::gif::{GifEncoder, Repeat},
+};
+use rocket::{FromForm, futures::lock::Mutex, http::ContentType, tokio::task};
+use webp_animation::Encoder as WebPEncoder;
+
+use crate::kennel::{
+    config::Config,
+    render::{IMAGE_FORMAT, IMAGE_HEIGHT, IMAGE_WIDTH},
+    tick::{NOTHING_TO_DRAW, Tick},
+};
+
+static DEFAULT_SIZE: u32 = 256;
+static MIN_DELAY_MS: u64 = 20;
+static MAX_DELAY_MS: u64 = 10000;
+// finished animations kept around, one per tick window and params
+static MAX_CACHED: usize = 8;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AnimationFormat {
+    Gif,
+    Apng,
+    WebP,
+}
+
+impl AnimationFormat {
+    fn parse(format: &str) -> Result<Self, String> {
+        match format.to_ascii_lowercase().as_str() {
+            "gif" => Ok(AnimationFormat::Gif),
+            "apng" | "png" => Ok(AnimationFormat::Apng),
+            "webp" => Ok(AnimationFormat::WebP),
+            _ => Err(format!("Unsupported animation format `{}`", format)),
+        }
+    }
+
+    pub fn content_type(&self) -> ContentType {
+        match self {
+            AnimationFormat::Gif => ContentType::GIF,
+            AnimationFormat::Apng => ContentType::new("image", "apng"),
+            AnimationFormat::WebP => ContentType::WEBP,
+        }
+    }
+}
+
+#[derive(FromForm)]
+pub struct AnimationQuery<'r> {
+    seconds: Option<u64>,
+    format: Option<&'r str>,
+    delay_ms: Option<u64>,
+    width: Option<u32>,
+    height: Option<u32>,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct AnimationParams {
+    seconds: u64,
+    format: AnimationFormat,
+    // `None` plays the ticks back at the pace they happened
+    delay_ms: Option<u64>,
+    width: u32,
+    height: u32,
+}
+
+impl AnimationParams {
+    pub fn parse(query: AnimationQuery<'_>, config: &Config) -> Result<Self, String> {
+        let format = query
+            .format
+            .map(AnimationFormat::parse)
+            .transpose()?
+            .unwrap_or(AnimationFormat::Gif);
+
+        let seconds = query.seconds.unwrap_or(config.animation_max_seconds);
+        if seconds == 0 || seconds > config.animation_max_seconds {
+            return Err(format!(
+                "`seconds` must be between 1 and {}",
+                config.animation_max_seconds
+            ));
+        }
+
+        let max_size = config.animation_max_size.max(1);
+        let clamp = |size: u32| size.clamp(1, max_size);
+        let (width, height) = match (query.width.map(clamp), query.height.map(clamp)) {
+            (Some(w), Some(h)) => (w, h),
+            (Some(w), None) => (w, clamp(w * IMAGE_HEIGHT / IMAGE_WIDTH)),
+            (None, Some(h)) => (clamp(h * IMAGE_WIDTH / IMAGE_HEIGHT), h),
+            (None, None) => {
+                let w = clamp(DEFAULT_SIZE);
+                (w, clamp(w * IMAGE_HEIGHT / IMAGE_WIDTH))
+            }
+        };
+
+        Ok(AnimationParams {
+            seconds,
+            format,
+            delay_ms: query
+                .delay_ms
+                .map(|delay| delay.clamp(MIN_DELAY_MS, MAX_DELAY_MS)),
+            width,
+            height,
+        })
+    }
+
+    pub fn format(&self) -> AnimationFormat {
+        self.format
+    }
+
+    pub fn window(&self) -> Duration {
+        Duration::from_secs(self.seconds)
+    }
+}
+
+// blocking, drawn straight at the animation size
+fn render_frame(tick: &Tick, width: u32, height: u32) -> Result<RgbaImage, String> {
+    let kennel = tick.kennel().ok_or(NOTHING_TO_DRAW)?;
+    let data = kennel.get_image(width, height, IMAGE_FORMAT)?;
+    image::load_from_memory_with_format(&data, IMAGE_FORMAT)
+        .map(|image| image.to_rgba8())
+        .map_err(|e| e.to_string())
+}
+
+// how long each frame stays up, the gap to the next tick unless overridden
+fn delays(ticks: &[Tick], delay_ms: Option<u64>) -> Vec<u64> {
+    let mut gaps = ticks
+        .windows(2)
+        .map(|pair| {
+            pair[1]
+                .timestamp
+                .duration_since(pair[0].timestamp)
+                .unwrap_or_default()
+                .as_millis() as u64
+        })
+        .collect::<Vec<_>>();
+    // the last frame holds as long as the one before it
+    gaps.push(gaps.last().copied().unwrap_or(1000));
+
+    gaps.into_iter()
+        .map(|gap| delay_ms.unwrap_or(gap).clamp(MIN_DELAY_MS, MAX_DELAY_MS))
+        .collect()
+}
+
+pub fn encode_gif(
+    frames: impl IntoIterator<Item = RgbaImage>,
+    delays: &[u64],
+) -> Result<Vec<u8>, String> {
+    let mut out = Cursor::new(Vec::new());
+    {
+        let mut encoder = GifEncoder::new_with_speed(&mut out, 10);
+        encoder
+            .set_repeat(Repeat::Infinite)
+            .map_err(|e| e.to_string())?;
+        let frames = frames.into_iter().zip(delays).map(|(frame, delay)| {
+            Frame::from_parts(frame, 0, 0, Delay::from_numer_denom_ms(*delay as u32, 1))
+        });
+        encoder.encode_frames(frames).map_err(|e| e.to_string())?;
+    }
+    Ok(out.into_inner())
+}
+
+fn encode_apng(frames: Vec<RgbaImage>, delays: &[u64]) -> Result<Vec<u8>, String> {
+    let (width, height) = frames[0].dimensions();
+    let mut out = Vec::new();
+    {
+        let mut encoder = png::Encoder::new(&mut out, width, height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder
+            .set_animated(frames.len() as u32, 0)
+            .map_err(|e| e.to_string())?;
+        let mut writer = encoder.write_header().map_err(|e| e.to_string())?;
+        for (frame, delay) in frames.iter().zip(delays) {
+            writer
+                .set_frame_delay(*delay as u16, 1000)
+                .map_err(|e| e.to_string())?;
+            writer
+                .write_image_data(frame.as_raw())
+                .map_err(|e| e.to_string())?;
+        }
+        writer.finish().map_err(|e| e.to_string())?;
+    }
+    Ok(out)
+}
+
+fn encode_webp(frames: Vec<RgbaImage>, delays: &[u64]) -> Result<Vec<u8>, String> {
+    let (width, height) = frames[0].dimensions();
+    let mut encoder = WebPEncoder::new((width, height)).map_err(|e| e.to_string())?;
+    let mut timestamp = 0;
+    for (frame, delay) in frames.iter().zip(delays) {
+        encoder
+            .add_frame(frame.as_raw(), timestamp)
+            .map_err(|e| e.to_string())?;
+        timestamp += *delay as i32;
+    }
+    let data = encoder.finalize(timestamp).map_err(|e| e.to_string())?;
+    Ok(data.to_vec())
+}
+
+fn encode(ticks: &[Tick], params: &AnimationParams) -> Result<Vec<u8>, String> {
+    let frames = ticks
+        .iter()
+        .map(|tick| render_frame(tick, params.width, params.height))
+        .collect::<Result<Vec<_>, _>>()?;
+    let delays = delays(ticks, params.delay_ms);
+
+    match params.format {
+        AnimationFormat::Gif => encode_gif(frames, &delays),
+        AnimationFormat::Apng => encode_apng(frames, &delays),
+        AnimationFormat::WebP => encode_webp(frames, &delays),
+    }
+}
+
+#[derive(Clone, PartialEq, Eq)]
+struct AnimationKey {
+    first: u64,
+    last: u64,
+    params: AnimationParams,
+}
+
+// encodes one animation at a time, so a burst of requests for the same window
+// only pays for it once
+#[derive(Default)]
+pub struct Animator {
+    cache: Mutex<VecDeque<(AnimationKey, Vec<u8>)>>,
+    encoding: Mutex<()>,
+}
+
+impl Animator {
+    async fn cached(&self, key: &AnimationKey) -> Option<Vec<u8>> {
+        let cache = self.cache.lock().await;
+        cache
+            .iter()
+            .find(|(cached, _)| cached == key)
+            .map(|(_, data)| data.clone())
+    }
+
+    // `ticks` oldest first, and never empty
+    pub async fn get(&self, ticks: Vec<Tick>, params: &AnimationParams) -> Result<Vec<u8>, String> {
+        let key = AnimationKey {
+            first: ticks[0].number,
+            last: ticks[ticks.len() - 1].number,
+            params: params.clone(),
+        };
+        if let Some(data) = self.cached(&key).await {
+            return Ok(data);
+        }
+
+        let _encoding = self.encoding.lock().await;
+        if let Some(data) = self.cached(&key).await {
+            return Ok(data);
+        }
+
+        let encode_params = params.clone();
+        let data = task::spawn_blocking(move || encode(&ticks, &encode_params))
+            .await
+            .map_err(|e| e.to_string())??;
+
+        let mut cache = self.cache.lock().await;
+        if cache.len() >= MAX_CACHED {
+            cache.pop_front();
+        }
+        cache.push_back((key, data.clone()));
+        Ok(data)
+    }
+
+    pub async fn clear(&self) {
+        self.cache.lock().await.clear();
+    }
+}
@@ -0,0 +1,119 @@
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use ws::Message;
+
+use crate::kennel::{
+    State,
+    encoding::Encoding,
+    feed::{Feed, Filter, Region},
+    tick::unix_millis,
+};
+
+// messages a client may send on the kennel socket
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Command {
+    // no creatures and no region subscribes to the whole kennel again
+    Subscribe {
+        creatures: Option<Vec<String>>,
+        region: Option<Region>,
+    },
+    Throttle {
+        every: u64,
+    },
+    Snapshot,
+    Ping,
+}
+
+impl Command {
+    fn name(&self) -> &'static str {
+        match self {
+            Command::Subscribe { .. } => "subscribe",
+            Command::Throttle { .. } => "throttle",
+            Command::Snapshot => "snapshot",
+            Command::Ping => "ping",
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct Request {
+    // echoed back in the reply so clients can match them up
+    id: Option<Value>,
+    #[serde(flatten)]
+    command: Command,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Reply {
+    Ack {
+        id: Option<Value>,
+        command: &'static str,
+    },
+    Pong {
+        id: Option<Value>,
+        timestamp: u64,
+    },
+    Error {
+        id: Option<Value>,
+        message: String,
+    },
+}
+
+impl Reply {
+    fn error(id: Option<Value>, message: &str) -> Self {
+        Reply::Error {
+            id,
+            message: message.to_string(),
+        }
+    }
+}
+
+// the frames to send back in response to `message`, in the feed's encoding
+pub async fn handle(message: Message, feed: &mut Feed, kennel: &State) -> Vec<Vec<u8>> {
+    let encoding = feed.encoding();
+    let encode = |reply: Reply| encoding.encode(&reply).into_iter().collect::<Vec<_>>();
+
+    // text frames are always JSON, binary frames use the negotiated encoding
+    let request = match message {
+        Message::Text(text) => Encoding::Json.decode::<Request>(text.as_bytes()),
+        Message::Binary(_) if encoding == Encoding::Json => {
+            return encode(Reply::error(None, "Binary messages are not supported"));
+        }
+        Message::Binary(data) => encoding.decode::<Request>(&data),
+        _ => return Vec::new(),
+    };
+
+    let Request { id, command } = match request {
+        Ok(request) => request,
+        Err(e) => return encode(Reply::error(None, &format!("Malformed command: {}", e))),
+    };
+
+    let name = command.name();
+    match command {
+        Command::Subscribe { region, .. } if region.is_some_and(|r| !r.is_valid()) => {
+            encode(Reply::error(id, "Region must have a positive size"))
+        }
+        Command::Subscribe { creatures, region } => {
+            feed.set_filter(Filter::new(creatures, region));
+            encode(Reply::Ack { id, command: name })
+        }
+        Command::Throttle { every: 0 } => encode(Reply::error(id, "Throttle must be at least 1")),
+        Command::Throttle { every } => {
+            feed.set_throttle(every);
+            encode(Reply::Ack { id, command: name })
+        }
+        Command::Snapshot => {
+            let mut replies = encode(Reply::Ack { id, command: name });
+            replies.extend(feed.snapshot(kennel.as_tick_json().await));
+            replies
+        }
+        Command::Ping => encode(Reply::Pong {
+            id,
+            timestamp: unix_millis(SystemTime::now()),
+        }),
+    }
+}
@@ -0,0 +1,121 @@
+use std::path::PathBuf;
+
+use rocket::figment::Figment;
+use serde::Deserialize;
+
+#[derive(Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SlowConsumerPolicy {
+    // skip the ticks a client fell behind on
+    Drop,
+    // close the socket once its queue overflows
+    Disconnect,
+}
+
+// read from the `kennel` table of Rocket.toml, or `ROCKET_KENNEL`
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct Config {
+    pub eager_render: bool,
+    pub tick_interval_ms: u64,
+    pub keyframe_interval: u64,
+    pub resume_window: usize,
+    pub ws_queue_size: usize,
+    pub ws_send_timeout_ms: u64,
+    pub slow_consumer_policy: SlowConsumerPolicy,
+    pub max_interpolation_hz: u32,
+    // reload when the data directory changes
+    pub watch: bool,
+    pub reload_debounce_ms: u64,
+    // how often to try again when the kennel failed to load
+    pub load_retry_ms: u64,
+    // creature state is saved here and restored on startup
+    pub snapshot_dir: PathBuf,
+    pub snapshot_interval_ms: u64,
+    pub snapshot_keep: usize,
+    // ticks kept for `/history`, and how many of those can still be rendered
+    pub history_ticks: usize,
+    pub history_kennels: usize,
+    // append every tick to rotating files under `record_dir`
+    pub record: bool,
+    pub record_dir: PathBuf,
+    pub record_max_bytes: u64,
+    pub record_keep: usize,
+    // a recording file or directory to play back instead of simulating, which
+    // turns `record` off
+    pub replay: Option<PathBuf>,
+    pub replay_speed: f64,
+    pub replay_loop: bool,
+    // caps on `/img/animated`
+    pub animation_max_seconds: u64,
+    pub animation_max_frames: usize,
+    pub animation_max_size: u32,
+    // the MJPEG stream at `/img/live`, frames per second are per viewer
+    pub live_size: u32,
+    pub live_quality: u8,
+    pub live_max_fps: u32,
+    // a frame every `timelapse_sample_ms`, compiled into one GIF per UTC day
+    pub timelapse: bool,
+    pub timelapse_dir: PathBuf,
+    pub timelapse_sample_ms: u64,
+    pub timelapse_size: u32,
+    pub timelapse_frame_delay_ms: u64,
+    pub timelapse_keep_days: usize,
+    // occupancy grid for `/heatmap`, older time counts half as much every
+    // half-life, 0 never forgets
+    pub heatmap_cells: u32,
+    pub heatmap_half_life_ms: u64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            eager_render: false,
+            tick_interval_ms: 1000,
+            keyframe_interval: 30,
+            resume_window: 60,
+            ws_queue_size: 8,
+            ws_send_timeout_ms: 5000,
+            slow_consumer_policy: SlowConsumerPolicy::Drop,
+            max_interpolation_hz: 30,
+            watch: true,
+            reload_debounce_ms: 500,
+            load_retry_ms: 30000,
+            snapshot_dir: PathBuf::from("./snapshots"),
+            snapshot_interval_ms: 60000,
+            snapshot_keep: 3,
+            history_ticks: 300,
+            history_kennels: 30,
+            record: false,
+            record_dir: PathBuf::from("./recordings"),
+            record_max_bytes: 64 * 1024 * 1024,
+            record_keep: 10,
+            replay: None,
+            replay_speed: 1.0,
+            replay_loop: true,
+            animation_max_seconds: 30,
+            animation_max_frames: 60,
+            animation_max_size: 512,
+            live_size: 512,
+            live_quality: 70,
+            live_max_fps: 5,
+            timelapse: true,
+            timelapse_dir: PathBuf::from("./timelapse"),
+            timelapse_sample_ms: 300000,
+            timelapse_size: 256,
+            timelapse_frame_delay_ms: 100,
+            timelapse_keep_days: 30,
+            heatmap_cells: 32,
+            heatmap_half_life_ms: 86400000,
+        }
+    }
+}
+
+impl Config {
+    pub fn from_figment(figment: &Figment) -> Result<Self, String> {
+        figment
+            .focus("kennel")
+            .extract::<Config>()
+            .map_err(|e| e.to_string())
+    }
+}
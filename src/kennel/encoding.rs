@@ -0,0 +1,136 @@
+use rocket::{
+    Request,
+    http::{Accept, ContentType, MediaType},
+    request::{FromRequest, Outcome},
+    response::{self, Responder},
+};
+use serde::{Serialize, de::DeserializeOwned};
+use ws::Message;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Encoding {
+    Json,
+    MessagePack,
+    Cbor,
+}
+
+// highest weighted media type `from_media_type` accepts, ties broken by header order
+pub fn negotiate<T>(
+    accept: Option<&Accept>,
+    from_media_type: impl Fn(&MediaType) -> Option<T>,
+) -> Option<T> {
+    let mut media_types = accept?.iter().collect::<Vec<_>>();
+    media_types.sort_by(|a, b| b.weight_or(1.0).total_cmp(&a.weight_or(1.0)));
+    media_types
+        .into_iter()
+        .find_map(|media_type| from_media_type(media_type.media_type()))
+}
+
+impl Encoding {
+    fn from_media_type(media_type: &MediaType) -> Option<Self> {
+        if media_type.top() != "application" {
+            return None;
+        }
+
+        match media_type.sub().as_str() {
+            "json" => Some(Encoding::Json),
+            "msgpack" | "x-msgpack" | "vnd.msgpack" => Some(Encoding::MessagePack),
+            "cbor" => Some(Encoding::Cbor),
+            _ => None,
+        }
+    }
+
+    pub fn from_accept(accept: Option<&Accept>) -> Self {
+        negotiate(accept, Encoding::from_media_type).unwrap_or(Encoding::Json)
+    }
+
+    pub fn from_protocol(protocol: &str) -> Option<Self> {
+        match protocol {
+            "kennel.json" => Some(Encoding::Json),
+            "kennel.msgpack" => Some(Encoding::MessagePack),
+            "kennel.cbor" => Some(Encoding::Cbor),
+            _ => None,
+        }
+    }
+
+    pub fn protocol(&self) -> &'static str {
+        match self {
+            Encoding::Json => "kennel.json",
+            Encoding::MessagePack => "kennel.msgpack",
+            Encoding::Cbor => "kennel.cbor",
+        }
+    }
+
+    pub fn content_type(&self) -> ContentType {
+        match self {
+            Encoding::Json => ContentType::JSON,
+            Encoding::MessagePack => ContentType::MsgPack,
+            Encoding::Cbor => ContentType::new("application", "cbor"),
+        }
+    }
+
+    pub fn encode<T: Serialize>(&self, value: &T) -> Result<Vec<u8>, String> {
+        match self {
+            Encoding::Json => serde_json::to_vec(value).map_err(|e| e.to_string()),
+            Encoding::MessagePack => rmp_serde::to_vec_named(value).map_err(|e| e.to_string()),
+            Encoding::Cbor => {
+                let mut out = Vec::new();
+                ciborium::into_writer(value, &mut out).map_err(|e| e.to_string())?;
+                Ok(out)
+            }
+        }
+    }
+
+    pub fn decode<T: DeserializeOwned>(&self, data: &[u8]) -> Result<T, String> {
+        match self {
+            Encoding::Json => serde_json::from_slice(data).map_err(|e| e.to_string()),
+            Encoding::MessagePack => rmp_serde::from_slice(data).map_err(|e| e.to_string()),
+            Encoding::Cbor => ciborium::from_reader(data).map_err(|e| e.to_string()),
+        }
+    }
+
+    // JSON goes out as text frames, everything else as binary frames
+    pub fn to_message(self, data: Vec<u8>) -> Message {
+        match self {
+            Encoding::Json => String::from_utf8(data)
+                .map(Message::Text)
+                .unwrap_or_else(|e| Message::Binary(e.into_bytes())),
+            _ => Message::Binary(data),
+        }
+    }
+}
+
+// the first encoding offered in `Sec-WebSocket-Protocol` that we support
+pub struct Protocol(pub Option<Encoding>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Protocol {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let encoding = request
+            .headers()
+            .get("Sec-WebSocket-Protocol")
+            .flat_map(|value| value.split(','))
+            .find_map(|protocol| Encoding::from_protocol(protocol.trim()));
+
+        Outcome::Success(Protocol(encoding))
+    }
+}
+
+// echoes the negotiated subprotocol on the upgrade response
+pub struct WithProtocol<R> {
+    pub inner: R,
+    pub protocol: Option<Encoding>,
+}
+
+impl<'r, 'o: 'r, R: Responder<'r, 'o>> Responder<'r, 'o> for WithProtocol<R> {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'o> {
+        let mut response = self.inner.respond_to(request)?;
+        if let Some(encoding) = self.protocol {
+            response.set_raw_header("Sec-WebSocket-Protocol", encoding.protocol());
+        }
+
+        Ok(response)
+    }
+}
@@ -0,0 +1,41 @@
+use rocket::{
+    Request,
+    request::{FromRequest, Outcome},
+    response::stream::Event,
+};
+
+use crate::kennel::{feed::Feed, json::TickJson};
+
+// the `id` of the last event an `EventSource` saw before reconnecting
+pub struct LastEventId(pub Option<String>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for LastEventId {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let id = request
+            .headers()
+            .get_one("Last-Event-ID")
+            .map(|id| id.to_string());
+
+        Outcome::Success(LastEventId(id))
+    }
+}
+
+// a named `reload` event when `tick` follows a reload of the data directory
+pub fn reload(tick: &TickJson) -> Option<Event> {
+    tick.reload()
+        .map(|reload| Event::json(reload).event("reload"))
+}
+
+// events carry the feed's resume token as their id
+pub fn event(feed: &mut Feed, tick: TickJson) -> Option<Event> {
+    let data = String::from_utf8(feed.next(tick)?).ok()?;
+    let event = Event::data(data);
+
+    Some(match feed.last_resume_token() {
+        Some(token) => event.id(token),
+        None => event,
+    })
+}
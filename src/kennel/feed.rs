@@ -0,0 +1,343 @@
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+
+use crate::kennel::{
+    encoding::Encoding,
+    json::{CreatureJson, DeltaJson, ReloadJson, TickJson},
+};
+
+#[derive(Clone, Copy)]
+pub enum FeedMode {
+    // the bare creature array
+    Creatures,
+    // a `TickJson` per tick
+    Envelope,
+    // a snapshot, then deltas with periodic keyframes
+    Deltas,
+}
+
+impl FeedMode {
+    pub fn from_query(envelope: bool, deltas: bool) -> Self {
+        match (envelope, deltas) {
+            (_, true) => FeedMode::Deltas,
+            (true, false) => FeedMode::Envelope,
+            (false, false) => FeedMode::Creatures,
+        }
+    }
+}
+
+// an area of the kennel, in the same coordinates as creature positions
+#[derive(Deserialize, Clone, Copy)]
+pub struct Region {
+    x: f64,
+    y: f64,
+    width: f64,
+    height: f64,
+}
+
+impl Region {
+    pub fn is_valid(&self) -> bool {
+        self.width > 0.0 && self.height > 0.0
+    }
+
+    fn contains(&self, creature: &CreatureJson) -> bool {
+        let position = creature.position();
+        (self.x..=self.x + self.width).contains(&position.x)
+            && (self.y..=self.y + self.height).contains(&position.y)
+    }
+}
+
+#[derive(Default)]
+pub struct Filter {
+    creatures: Option<HashSet<String>>,
+    region: Option<Region>,
+}
+
+impl Filter {
+    pub fn new(creatures: Option<Vec<String>>, region: Option<Region>) -> Self {
+        Filter {
+            creatures: creatures.map(HashSet::from_iter),
+            region,
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.creatures.is_none() && self.region.is_none()
+    }
+
+    fn matches(&self, creature: &CreatureJson) -> bool {
+        self.creatures
+            .as_ref()
+            .is_none_or(|ids| ids.contains(creature.id()))
+            && self
+                .region
+                .as_ref()
+                .is_none_or(|region| region.contains(creature))
+    }
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum FeedMessage<'a> {
+    Snapshot {
+        resume: String,
+        #[serde(flatten)]
+        tick: &'a TickJson,
+    },
+    Delta {
+        resume: String,
+        #[serde(flatten)]
+        delta: DeltaJson,
+    },
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename = "reload")]
+struct ReloadMessage<'a> {
+    tick: u64,
+    #[serde(flatten)]
+    reload: &'a ReloadJson,
+}
+
+// per-connection encoder for the kennel feed
+pub struct Feed {
+    mode: FeedMode,
+    encoding: Encoding,
+    instance: String,
+    keyframe_interval: u64,
+    last: Option<TickJson>,
+    since_keyframe: u64,
+    filter: Filter,
+    every: u64,
+}
+
+impl Feed {
+    pub fn new(
+        mode: FeedMode,
+        encoding: Encoding,
+        instance: String,
+        keyframe_interval: u64,
+        resumed: Option<TickJson>,
+    ) -> Self {
+        Feed {
+            mode,
+            encoding,
+            instance,
+            keyframe_interval,
+            last: resumed,
+            since_keyframe: 0,
+            filter: Filter::default(),
+            every: 1,
+        }
+    }
+
+    pub fn encoding(&self) -> Encoding {
+        self.encoding
+    }
+
+    pub fn set_filter(&mut self, filter: Filter) {
+        self.filter = filter;
+    }
+
+    // whether `creature` passes the client's subscription filter
+    pub fn includes(&self, creature: &CreatureJson) -> bool {
+        self.filter.matches(creature)
+    }
+
+    // only send every `every`th tick
+    pub fn set_throttle(&mut self, every: u64) {
+        self.every = every.max(1);
+    }
+
+    pub fn every(&self) -> u64 {
+        self.every
+    }
+
+    // the message for `tick` as if the client had nothing yet
+    pub fn snapshot(&mut self, tick: TickJson) -> Option<Vec<u8>> {
+        self.last = None;
+        self.next(tick)
+    }
+
+    // the message to send for `tick`, if the client doesn't already have it
+    pub fn next(&mut self, tick: TickJson) -> Option<Vec<u8>> {
+        if self
+            .last
+            .as_ref()
+            .is_some_and(|last| last.tick() + self.every > tick.tick())
+        {
+            return None;
+        }
+
+        let tick = if self.filter.is_empty() {
+            tick
+        } else {
+            tick.filtered(|creature| self.filter.matches(creature))
+        };
+
+        let encoding = self.encoding;
+        let message = match self.mode {
+            FeedMode::Creatures => encoding.encode(tick.creatures()),
+            FeedMode::Envelope => encoding.encode(&tick),
+            FeedMode::Deltas => encoding.encode(&self.delta_message(&tick)),
+        };
+
+        self.last = Some(tick);
+        message.ok()
+    }
+
+    // tells the client about creatures a reload added or removed, ahead of `tick` itself
+    pub fn reload_notice(&self, tick: &TickJson) -> Option<Vec<u8>> {
+        let message = ReloadMessage {
+            tick: tick.tick(),
+            reload: tick.reload()?,
+        };
+        self.encoding.encode(&message).ok()
+    }
+
+    fn resume_token(&self, tick: u64) -> String {
+        format!("{}.{}", self.instance, tick)
+    }
+
+    // token for resuming after the last message sent
+    pub fn last_resume_token(&self) -> Option<String> {
+        self.last
+            .as_ref()
+            .map(|last| self.resume_token(last.tick()))
+    }
+
+    fn delta_message<'a>(&mut self, tick: &'a TickJson) -> FeedMessage<'a> {
+        let resume = self.resume_token(tick.tick());
+        let base = self
+            .last
+            .as_ref()
+            .filter(|_| self.since_keyframe < self.keyframe_interval);
+
+        match base {
+            Some(base) => {
+                self.since_keyframe += 1;
+                FeedMessage::Delta {
+                    resume,
+                    delta: DeltaJson::between(base, tick),
+                }
+            }
+            None => {
+                self.since_keyframe = 0;
+                FeedMessage::Snapshot { resume, tick }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{Value, json};
+
+    use super::*;
+
+    fn tick(number: u64, x: f64) -> TickJson {
+        serde_json::from_value(json!({
+            "schema": 1,
+            "tick": number,
+            "timestamp": number * 1000,
+            "interval_ms": 1000,
+            "world": { "width": 10.0, "height": 10.0 },
+            "creatures": [{
+                "id": "a",
+                "url": "https://example.com/a",
+                "display_name": "A",
+                "radius": 1.0,
+                "position": { "x": x, "y": 0.0 },
+                "state": "idle",
+                "sprite_path": "/api/kennel-club/a/img/idle/0",
+            }],
+        }))
+        .unwrap()
+    }
+
+    fn feed(mode: FeedMode, resumed: Option<TickJson>) -> Feed {
+        Feed::new(mode, Encoding::Json, "instance".to_string(), 3, resumed)
+    }
+
+    fn decode(data: Option<Vec<u8>>) -> Value {
+        serde_json::from_slice(&data.expect("a message")).unwrap()
+    }
+
+    #[test]
+    fn throttle_sends_every_nth_tick() {
+        let mut feed = feed(FeedMode::Envelope, None);
+        feed.set_throttle(3);
+
+        let sent = (0..10)
+            .filter(|number| feed.next(tick(*number, 0.0)).is_some())
+            .collect::<Vec<_>>();
+        assert_eq!(sent, vec![0, 3, 6, 9]);
+    }
+
+    #[test]
+    fn throttle_counts_from_the_last_tick_sent() {
+        let mut feed = feed(FeedMode::Envelope, None);
+        feed.set_throttle(2);
+
+        // a gap in the ticks doesn't shift which ones are due
+        assert!(feed.next(tick(0, 0.0)).is_some());
+        assert!(feed.next(tick(3, 0.0)).is_some());
+        assert!(feed.next(tick(4, 0.0)).is_none());
+        assert!(feed.next(tick(5, 0.0)).is_some());
+    }
+
+    #[test]
+    fn ticks_already_sent_are_not_sent_again() {
+        let mut feed = feed(FeedMode::Envelope, None);
+
+        assert!(feed.next(tick(5, 0.0)).is_some());
+        assert!(feed.next(tick(5, 0.0)).is_none());
+        assert!(feed.next(tick(4, 0.0)).is_none());
+        assert_eq!(feed.last_resume_token(), Some("instance.5".to_string()));
+    }
+
+    #[test]
+    fn resuming_sends_a_delta_against_the_resumed_tick() {
+        let mut feed = feed(FeedMode::Deltas, Some(tick(5, 0.0)));
+
+        // the resumed tick itself is what the client already has
+        assert!(feed.next(tick(5, 0.0)).is_none());
+
+        let message = decode(feed.next(tick(6, 2.0)));
+        assert_eq!(message["type"], "delta");
+        assert_eq!(message["base"], 5);
+        assert_eq!(message["tick"], 6);
+        assert_eq!(message["resume"], "instance.6");
+        assert_eq!(
+            message["changed"],
+            json!([{ "id": "a", "position": { "x": 2.0, "y": 0.0 } }])
+        );
+    }
+
+    #[test]
+    fn deltas_start_with_a_snapshot_and_repeat_it_every_keyframe_interval() {
+        let mut feed = feed(FeedMode::Deltas, None);
+
+        let types = (0..9)
+            .map(|number| decode(feed.next(tick(number, number as f64)))["type"].clone())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            types,
+            vec![
+                "snapshot", "delta", "delta", "delta", "snapshot", "delta", "delta", "delta",
+                "snapshot",
+            ]
+        );
+    }
+
+    #[test]
+    fn snapshot_resends_the_whole_tick() {
+        let mut feed = feed(FeedMode::Deltas, Some(tick(5, 0.0)));
+
+        let message = decode(feed.snapshot(tick(5, 0.0)));
+        assert_eq!(message["type"], "snapshot");
+        assert_eq!(message["tick"], 5);
+        assert_eq!(message["creatures"][0]["id"], "a");
+    }
+}
@@ -0,0 +1,19 @@
+use std::{
+    fs::{self, File},
+    io::Write,
+    path::Path,
+};
+
+// writes through a temporary file next to `path`, so neither readers nor a
+// crash ever see half of it
+pub fn write_atomic(path: &Path, data: &[u8]) -> Result<(), String> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+
+    let partial = path.with_extension("partial");
+    let mut file = File::create(&partial).map_err(|e| e.to_string())?;
+    file.write_all(data).map_err(|e| e.to_string())?;
+    file.sync_all().map_err(|e| e.to_string())?;
+    fs::rename(&partial, path).map_err(|e| e.to_string())
+}
@@ -0,0 +1,250 @@
+use std::{
+    collections::HashMap,
+    fs,
+    io::Cursor,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use image::{DynamicImage, Rgba, RgbaImage};
+use kennel_club::{ImageFormat, Kennel};
+use serde::{Deserialize, Serialize};
+
+use crate::kennel::{
+    config::Config,
+    files::write_atomic,
+    json::{PositionJson, TickJson, WorldJson},
+};
+
+static HEATMAP_VERSION: u32 = 1;
+static HEATMAP_FILE: &str = "heatmap.json";
+// creatures whose whole grid decayed below this are forgotten
+static FORGET_BELOW: f64 = 1e-6;
+
+// where the heatmap is kept between restarts, next to the snapshots
+pub fn heatmap_path(config: &Config) -> PathBuf {
+    config.snapshot_dir.join(HEATMAP_FILE)
+}
+
+#[derive(Serialize)]
+pub struct HeatmapJson {
+    cells: u32,
+    half_life_ms: u64,
+    updated_at: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    creature: Option<String>,
+    // sum over all cells, in seconds after decay
+    total_seconds: f64,
+    // rows from the top of the kennel, each cell the seconds spent there
+    grid: Vec<Vec<f64>>,
+}
+
+// seconds spent in each cell of a `cells` x `cells` grid over the kennel,
+// overall and per creature, fading with a half-life
+#[derive(Serialize, Deserialize)]
+pub struct Heatmap {
+    version: u32,
+    cells: u32,
+    // timestamp of the last tick counted, decay runs from here
+    updated_at: u64,
+    total: Vec<f64>,
+    creatures: HashMap<String, Vec<f64>>,
+    #[serde(skip)]
+    half_life: Duration,
+}
+
+impl Heatmap {
+    pub fn new(config: &Config) -> Self {
+        let cells = config.heatmap_cells.clamp(1, 256);
+        Heatmap {
+            version: HEATMAP_VERSION,
+            cells,
+            updated_at: 0,
+            total: vec![0.0; (cells * cells) as usize],
+            creatures: HashMap::new(),
+            half_life: Duration::from_millis(config.heatmap_half_life_ms),
+        }
+    }
+
+    // the saved heatmap if it still fits the configured grid, otherwise a
+    // fresh one
+    pub fn load(path: &Path, config: &Config) -> Self {
+        let fresh = Heatmap::new(config);
+        let data = match fs::read(path) {
+            Ok(data) => data,
+            Err(_) => return fresh,
+        };
+
+        match serde_json::from_slice::<Heatmap>(&data) {
+            Ok(saved) if saved.version == HEATMAP_VERSION && saved.cells == fresh.cells => {
+                log::info!("Restored kennel heatmap from {}", path.display());
+                Heatmap {
+                    half_life: fresh.half_life,
+                    ..saved
+                }
+            }
+            Ok(_) => {
+                log::warn!("Discarding heatmap {}, the grid changed", path.display());
+                fresh
+            }
+            Err(e) => {
+                log::warn!("Discarding heatmap {}: {}", path.display(), e);
+                fresh
+            }
+        }
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, String> {
+        serde_json::to_vec(self).map_err(|e| e.to_string())
+    }
+
+    // blocking
+    pub fn save(path: &Path, data: &[u8]) -> Result<(), String> {
+        write_atomic(path, data)
+    }
+
+    fn decay(&mut self, now: u64) {
+        if self.half_life.is_zero() || self.updated_at == 0 || now <= self.updated_at {
+            return;
+        }
+
+        let elapsed = (now - self.updated_at) as f64;
+        let factor = 0.5f64.powf(elapsed / self.half_life.as_millis() as f64);
+        for cell in self.total.iter_mut() {
+            *cell *= factor;
+        }
+        self.creatures.retain(|_, grid| {
+            for cell in grid.iter_mut() {
+                *cell *= factor;
+            }
+            grid.iter().sum::<f64>() >= FORGET_BELOW
+        });
+    }
+
+    fn cell(&self, position: PositionJson, world: &WorldJson) -> Option<usize> {
+        let (x, y) = world.fraction(position)?;
+        let last = self.cells as usize - 1;
+        let column = ((x * self.cells as f64) as usize).min(last);
+        let row = ((y * self.cells as f64) as usize).min(last);
+        Some(row * self.cells as usize + column)
+    }
+
+    // every creature counts for as long as the tick lasts
+    pub fn add(&mut self, json: &TickJson) {
+        self.decay(json.timestamp());
+        self.updated_at = json.timestamp();
+
+        let seconds = json.interval().as_secs_f64();
+        let size = self.total.len();
+        for creature in json.creatures().iter() {
+            let Some(cell) = self.cell(creature.position(), json.world()) else {
+                continue;
+            };
+
+            self.total[cell] += seconds;
+            self.creatures
+                .entry(creature.id().to_string())
+                .or_insert_with(|| vec![0.0; size])[cell] += seconds;
+        }
+    }
+
+    // the overall grid, or the one of a single creature
+    pub fn grid(&self, creature: Option<&str>) -> Option<&[f64]> {
+        match creature {
+            Some(id) => self.creatures.get(id).map(Vec::as_slice),
+            None => Some(&self.total),
+        }
+    }
+
+    pub fn to_json(&self, creature: Option<&str>) -> Option<HeatmapJson> {
+        let grid = self.grid(creature)?;
+        Some(HeatmapJson {
+            cells: self.cells,
+            half_life_ms: self.half_life.as_millis() as u64,
+            updated_at: self.updated_at,
+            creature: creature.map(str::to_string),
+            total_seconds: grid.iter().sum(),
+            grid: grid
+                .chunks(self.cells as usize)
+                .map(<[f64]>::to_vec)
+                .collect(),
+        })
+    }
+
+    pub fn cells(&self) -> u32 {
+        self.cells
+    }
+}
+
+// cold to hot, blue through yellow to red
+fn ramp(value: f64) -> [u8; 3] {
+    let lerp = |from: f64, to: f64, t: f64| (from + (to - from) * t).round() as u8;
+    if value < 0.5 {
+        let t = value * 2.0;
+        [
+            lerp(0.0, 255.0, t),
+            lerp(0.0, 255.0, t),
+            lerp(255.0, 0.0, t),
+        ]
+    } else {
+        let t = (value - 0.5) * 2.0;
+        [255, lerp(255.0, 0.0, t), 0]
+    }
+}
+
+// tints `base` cell by cell, relative to the busiest cell
+fn overlay(base: &mut RgbaImage, grid: &[f64], cells: u32) {
+    let max = grid.iter().copied().fold(0.0, f64::max);
+    if max <= 0.0 {
+        return;
+    }
+
+    let (width, height) = base.dimensions();
+    for (x, y, pixel) in base.enumerate_pixels_mut() {
+        let column = (x as u64 * cells as u64 / width as u64) as usize;
+        let row = (y as u64 * cells as u64 / height as u64) as usize;
+        let value = grid[row * cells as usize + column] / max;
+        if value <= 0.0 {
+            continue;
+        }
+
+        let alpha = 0.2 + 0.5 * value;
+        let color = ramp(value);
+        let Rgba([r, g, b, a]) = *pixel;
+        let blend = |under: u8, over: u8| {
+            (under as f64 * (1.0 - alpha) + over as f64 * alpha).round() as u8
+        };
+        *pixel = Rgba([
+            blend(r, color[0]),
+            blend(g, color[1]),
+            blend(b, color[2]),
+            a,
+        ]);
+    }
+}
+
+// blocking, the kennel `size` pixels along its longer side with `grid` on top
+pub fn render(kennel: &Kennel, grid: &[f64], cells: u32, size: u32) -> Result<Vec<u8>, String> {
+    let (width, height) = if kennel.width() >= kennel.height() {
+        (
+            size,
+            (size as f64 * kennel.height() / kennel.width()).round() as u32,
+        )
+    } else {
+        (
+            (size as f64 * kennel.width() / kennel.height()).round() as u32,
+            size,
+        )
+    };
+    let data = kennel.get_image(width.max(1), height.max(1), ImageFormat::Png)?;
+    let mut image = image::load_from_memory_with_format(&data, ImageFormat::Png)
+        .map_err(|e| e.to_string())?
+        .to_rgba8();
+    overlay(&mut image, grid, cells);
+
+    let mut out = Cursor::new(Vec::new());
+    DynamicImage::ImageRgba8(image)
+        .write_to(&mut out, ImageFormat::Png)
+        .map_err(|e| e.to_string())?;
+    Ok(out.into_inner())
+}
@@ -0,0 +1,95 @@
+use std::{collections::VecDeque, time::Duration};
+
+use serde::Serialize;
+
+use crate::kennel::{json::TickJson, tick::Tick};
+
+#[derive(Serialize)]
+pub struct HistoryJson {
+    oldest: Option<u64>,
+    newest: Option<u64>,
+    ticks: Vec<TickJson>,
+}
+
+// the last few ticks, bounded twice since a whole `Kennel` weighs a lot more
+// than its JSON
+pub struct History {
+    ticks: VecDeque<TickJson>,
+    kennels: VecDeque<Tick>,
+    max_ticks: usize,
+    max_kennels: usize,
+}
+
+impl History {
+    pub fn new(max_ticks: usize, max_kennels: usize) -> Self {
+        History {
+            ticks: VecDeque::new(),
+            kennels: VecDeque::new(),
+            max_ticks: max_ticks.max(1),
+            max_kennels,
+        }
+    }
+
+    pub fn push(&mut self, json: TickJson, tick: &Tick) {
+        if self.ticks.len() >= self.max_ticks {
+            self.ticks.pop_front();
+        }
+        self.ticks.push_back(json);
+
+        if self.max_kennels == 0 {
+            return;
+        }
+        if self.kennels.len() >= self.max_kennels {
+            self.kennels.pop_front();
+        }
+        self.kennels.push_back(tick.clone());
+    }
+
+    pub fn get(&self, tick: u64) -> Option<&TickJson> {
+        self.ticks.iter().find(|json| json.tick() == tick)
+    }
+
+    pub fn kennel_at(&self, tick: u64) -> Option<&Tick> {
+        self.kennels.iter().find(|kept| kept.number == tick)
+    }
+
+    // the kennels of the last `window`, up to `limit` of the newest ones,
+    // oldest first
+    pub fn kennels_within(&self, window: Duration, limit: usize) -> Vec<Tick> {
+        let Some(newest) = self.kennels.back() else {
+            return Vec::new();
+        };
+        let since = newest
+            .timestamp
+            .checked_sub(window)
+            .unwrap_or(newest.timestamp);
+
+        let mut kennels = self
+            .kennels
+            .iter()
+            .rev()
+            .take_while(|kept| kept.timestamp >= since)
+            .take(limit)
+            .cloned()
+            .collect::<Vec<_>>();
+        kennels.reverse();
+        kennels
+    }
+
+    // up to `limit` ticks after `since`, oldest first
+    pub fn since(&self, since: Option<u64>, limit: usize) -> HistoryJson {
+        let ticks = self
+            .ticks
+            .iter()
+            .filter(|json| since.is_none_or(|since| json.tick() > since))
+            .take(limit)
+            .cloned()
+            .collect();
+
+        HistoryJson {
+            oldest: self.ticks.front().map(|json| json.tick()),
+            newest: self.ticks.back().map(|json| json.tick()),
+            ticks,
+        }
+    }
+}
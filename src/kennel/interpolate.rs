@@ -0,0 +1,163 @@
+use std::{
+    collections::HashMap,
+    pin::Pin,
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use rocket::{
+    futures::Stream,
+    tokio::time::{self, Interval, MissedTickBehavior},
+};
+use serde::Serialize;
+
+use crate::kennel::{
+    feed::Feed,
+    json::{CreatureJson, PositionJson, TickJson},
+};
+
+#[derive(Serialize)]
+struct CreatureFrameJson<'a> {
+    id: &'a str,
+    position: PositionJson,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename = "frame")]
+struct FrameJson<'a> {
+    tick: u64,
+    // how far along from the previous tick to `tick`, 0 to 1
+    progress: f64,
+    creatures: Vec<CreatureFrameJson<'a>>,
+}
+
+// blends creature positions between the last two ticks a client received,
+// so frames trail the simulation by one tick, or by the feed's throttle
+pub struct Interpolator {
+    from: Option<TickJson>,
+    to: Option<TickJson>,
+    received: Instant,
+}
+
+impl Interpolator {
+    pub fn new(tick: TickJson) -> Self {
+        Interpolator {
+            from: None,
+            to: Some(tick),
+            received: Instant::now(),
+        }
+    }
+
+    pub fn push(&mut self, tick: TickJson) {
+        self.from = self.to.replace(tick);
+        self.received = Instant::now();
+    }
+
+    // how far along from the previous tick to the last one, 0 to 1, over as
+    // many intervals as there were ticks between them
+    fn progress(&self) -> f64 {
+        let Some(to) = self.to.as_ref() else {
+            return 1.0;
+        };
+        let ticks = self
+            .from
+            .as_ref()
+            .map_or(1, |from| to.tick().saturating_sub(from.tick()).max(1));
+        let span = to.interval().as_secs_f64() * ticks as f64;
+        if span > 0.0 {
+            (self.received.elapsed().as_secs_f64() / span).min(1.0)
+        } else {
+            1.0
+        }
+    }
+
+    // once there, frames would only repeat the last tick
+    pub fn is_caught_up(&self) -> bool {
+        self.progress() >= 1.0
+    }
+
+    // the frame for right now, filtered and encoded like the rest of `feed`
+    pub fn frame(&self, feed: &Feed) -> Option<Vec<u8>> {
+        let to = self.to.as_ref()?;
+        let progress = self.progress();
+
+        let before = self
+            .from
+            .iter()
+            .flat_map(|from| from.creatures().iter())
+            .map(|creature| (creature.id(), creature))
+            .collect::<HashMap<_, _>>();
+
+        let creatures = to
+            .creatures()
+            .iter()
+            .filter(|creature| feed.includes(creature))
+            .map(|creature| CreatureFrameJson {
+                id: creature.id(),
+                position: lerp(before.get(creature.id()).copied(), creature, progress),
+            })
+            .collect();
+
+        let frame = FrameJson {
+            tick: to.tick(),
+            progress,
+            creatures,
+        };
+        feed.encoding().encode(&frame).ok()
+    }
+}
+
+// creatures that just arrived start where they are
+fn lerp(from: Option<&CreatureJson>, to: &CreatureJson, progress: f64) -> PositionJson {
+    let end = to.position();
+    let start = from.map(|creature| creature.position()).unwrap_or(end);
+
+    PositionJson {
+        x: start.x + (end.x - start.x) * progress,
+        y: start.y + (end.y - start.y) * progress,
+    }
+}
+
+// fires at the frame rate from a tick until the frames catch up with it, so a
+// paused kennel gets no frames at all
+pub struct FrameTimer {
+    // `None` when interpolation is off
+    period: Option<Duration>,
+    interval: Option<Interval>,
+}
+
+impl FrameTimer {
+    pub fn new(hz: Option<u32>) -> Self {
+        FrameTimer {
+            period: hz.map(|hz| Duration::from_secs(1) / hz.max(1)),
+            interval: None,
+        }
+    }
+
+    // frames thin out along with ticks when the feed only sends every `every`th
+    pub fn start(&mut self, every: u64) {
+        let Some(period) = self.period else {
+            return;
+        };
+        let period = period * every.clamp(1, u32::MAX as u64) as u32;
+        let mut frames = time::interval_at(time::Instant::now() + period, period);
+        frames.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        self.interval = Some(frames);
+    }
+
+    pub fn stop(&mut self) {
+        self.interval = None;
+    }
+}
+
+// never ends, stopped it's just pending until started again
+impl Stream for FrameTimer {
+    type Item = ();
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        match self.get_mut().interval.as_mut() {
+            Some(interval) => interval.poll_tick(cx).map(|_| Some(())),
+            None => Poll::Pending,
+        }
+    }
+}
@@ -1,44 +1,128 @@
-use kennel_club::{creature::{self, Creature}, math::Vec2, Kennel};
-use serde::Serialize;
+use std::{
+    collections::{HashMap, HashSet},
+    time::Duration,
+};
 
-#[derive(Serialize, Clone)]
+use kennel_club::{Kennel, creature::Creature};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::kennel::tick::{Source, Tick, unix_millis};
+
+static SCHEMA_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct PositionJson {
+    pub x: f64,
+    pub y: f64,
+}
+
+// owns all of its fields, so recorded ticks read back without the kennel
+#[derive(Serialize, Deserialize, Clone)]
 pub struct CreatureJson {
     id: String,
     url: String,
     display_name: String,
     radius: f64,
-    position: Vec2,
-    state: creature::State,
+    position: PositionJson,
+    state: Value,
     sprite_path: String,
 }
 
 impl From<&Creature> for CreatureJson {
     fn from(creature: &Creature) -> Self {
-        let sprite_path = format!("/api/kennel-club/{}/img/{}/{}", creature.id, creature.sprite_state.to_string(), creature.sprite_state_duration);
+        let sprite_path = format!(
+            "/api/kennel-club/{}/img/{}/{}",
+            creature.id,
+            creature.sprite_state.to_string(),
+            creature.sprite_state_duration
+        );
         CreatureJson {
             id: creature.id.clone(),
             url: creature.url.clone(),
             display_name: creature.display_name.clone(),
             radius: creature.radius,
-            position: creature.position,
-            state: creature.creature_state.clone(),
+            position: PositionJson {
+                x: creature.position.x,
+                y: creature.position.y,
+            },
+            state: serde_json::to_value(&creature.creature_state).unwrap_or(Value::Null),
             sprite_path,
         }
     }
 }
 
 impl CreatureJson {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
     pub fn url(&self) -> String {
         self.url.clone()
     }
+
+    pub fn position(&self) -> PositionJson {
+        self.position
+    }
+
+    // the variant of `state` as it's serialized, without any of its data
+    pub fn state_name(&self) -> String {
+        match &self.state {
+            Value::String(name) => name.clone(),
+            Value::Object(fields) => match fields.get("type") {
+                Some(Value::String(name)) => name.clone(),
+                _ => fields.keys().next().cloned().unwrap_or_default(),
+            },
+            _ => "unknown".to_string(),
+        }
+    }
+
+    // the fields of `self` that differ from `before`, along with the id
+    fn changed_since(&self, before: &CreatureJson) -> Option<Map<String, Value>> {
+        let (Ok(Value::Object(before)), Ok(Value::Object(after))) =
+            (serde_json::to_value(before), serde_json::to_value(self))
+        else {
+            return None;
+        };
+
+        let mut fields = after
+            .into_iter()
+            .filter(|(key, value)| before.get(key) != Some(value))
+            .collect::<Map<String, Value>>();
+        if fields.is_empty() {
+            return None;
+        }
+
+        fields.insert("id".to_string(), Value::String(self.id.clone()));
+        Some(fields)
+    }
 }
 
-#[derive(Serialize, Clone)]
+#[derive(Serialize, Deserialize, Clone)]
 #[serde(transparent)]
 pub struct KennelJson {
     creatures: Vec<CreatureJson>,
 }
 
+impl KennelJson {
+    pub fn iter(&self) -> impl Iterator<Item = &CreatureJson> {
+        self.creatures.iter()
+    }
+
+    pub fn into_creatures(self) -> Vec<CreatureJson> {
+        self.creatures
+    }
+}
+
+impl From<&Source> for KennelJson {
+    fn from(source: &Source) -> Self {
+        match source {
+            Source::Kennel(kennel) => KennelJson::from(kennel.as_ref()),
+            Source::Recorded(json) => json.creatures.clone(),
+        }
+    }
+}
+
 impl From<&Kennel> for KennelJson {
     fn from(kennel: &Kennel) -> Self {
         Self {
@@ -50,3 +134,343 @@ impl From<&Kennel> for KennelJson {
         }
     }
 }
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct WorldJson {
+    width: f64,
+    height: f64,
+}
+
+impl WorldJson {
+    // how far across the world `position` is, 0 to 1 from the top left on
+    // both axes, positions past an edge count as on it
+    pub fn fraction(&self, position: PositionJson) -> Option<(f64, f64)> {
+        let has_area = self.width > 0.0 && self.height > 0.0;
+        if !has_area || !position.x.is_finite() || !position.y.is_finite() {
+            return None;
+        }
+
+        Some((
+            (position.x / self.width).clamp(0.0, 1.0),
+            (position.y / self.height).clamp(0.0, 1.0),
+        ))
+    }
+}
+
+impl From<&Kennel> for WorldJson {
+    fn from(kennel: &Kennel) -> Self {
+        Self {
+            width: kennel.width(),
+            height: kennel.height(),
+        }
+    }
+}
+
+impl From<&Source> for WorldJson {
+    fn from(source: &Source) -> Self {
+        match source {
+            Source::Kennel(kennel) => WorldJson::from(kennel.as_ref()),
+            Source::Recorded(json) => json.world.clone(),
+        }
+    }
+}
+
+// creatures a reload of the data directory brought in or took away
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ReloadJson {
+    added: Vec<String>,
+    removed: Vec<String>,
+}
+
+impl ReloadJson {
+    pub fn loaded(creatures: &KennelJson) -> Self {
+        let mut added = creatures
+            .iter()
+            .map(|creature| creature.id.clone())
+            .collect::<Vec<_>>();
+        added.sort();
+        Self {
+            added,
+            removed: Vec::new(),
+        }
+    }
+
+    pub fn between(before: &KennelJson, after: &KennelJson) -> Self {
+        let ids = |creatures: &KennelJson| {
+            creatures
+                .iter()
+                .map(|creature| creature.id.clone())
+                .collect::<HashSet<_>>()
+        };
+        let (before, after) = (ids(before), ids(after));
+
+        let mut added = after.difference(&before).cloned().collect::<Vec<_>>();
+        let mut removed = before.difference(&after).cloned().collect::<Vec<_>>();
+        added.sort();
+        removed.sort();
+        Self { added, removed }
+    }
+}
+
+// last message on a creature's own stream, once it left the kennel
+#[derive(Serialize)]
+#[serde(tag = "type", rename = "removed")]
+pub struct RemovedJson<'a> {
+    id: &'a str,
+    tick: u64,
+}
+
+impl<'a> RemovedJson<'a> {
+    pub fn new(id: &'a str, tick: u64) -> Self {
+        RemovedJson { id, tick }
+    }
+}
+
+// opt-in envelope around a kennel state, see `?envelope`
+#[derive(Serialize, Deserialize, Clone)]
+pub struct TickJson {
+    schema: u32,
+    tick: u64,
+    timestamp: u64,
+    interval_ms: u64,
+    world: WorldJson,
+    creatures: KennelJson,
+    // only on the first tick after a reload
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    reload: Option<ReloadJson>,
+}
+
+impl TickJson {
+    pub fn new(tick: &Tick, interval: Duration) -> Self {
+        Self {
+            schema: SCHEMA_VERSION,
+            tick: tick.number,
+            timestamp: unix_millis(tick.timestamp),
+            interval_ms: interval.as_millis() as u64,
+            world: WorldJson::from(&tick.source),
+            creatures: KennelJson::from(&tick.source),
+            reload: None,
+        }
+    }
+
+    pub fn with_reload(self, reload: Option<ReloadJson>) -> Self {
+        Self { reload, ..self }
+    }
+
+    pub fn reload(&self) -> Option<&ReloadJson> {
+        self.reload.as_ref()
+    }
+
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    pub fn timestamp(&self) -> u64 {
+        self.timestamp
+    }
+
+    pub fn interval(&self) -> Duration {
+        Duration::from_millis(self.interval_ms)
+    }
+
+    pub fn world(&self) -> &WorldJson {
+        &self.world
+    }
+
+    pub fn creatures(&self) -> &KennelJson {
+        &self.creatures
+    }
+
+    pub fn creature(&self, id: &str) -> Option<&CreatureJson> {
+        self.creatures
+            .creatures
+            .iter()
+            .find(|creature| creature.id == id)
+    }
+
+    // a copy of this tick with only the creatures matching `predicate`
+    pub fn filtered(&self, predicate: impl Fn(&CreatureJson) -> bool) -> Self {
+        let creatures = self
+            .creatures
+            .creatures
+            .iter()
+            .filter(|creature| predicate(creature))
+            .cloned()
+            .collect();
+
+        Self {
+            world: self.world.clone(),
+            creatures: KennelJson { creatures },
+            reload: self.reload.clone(),
+            ..*self
+        }
+    }
+}
+
+#[derive(Serialize, Clone)]
+pub struct DeltaJson {
+    tick: u64,
+    base: u64,
+    timestamp: u64,
+    changed: Vec<Map<String, Value>>,
+    added: Vec<CreatureJson>,
+    removed: Vec<String>,
+}
+
+impl DeltaJson {
+    pub fn between(base: &TickJson, tick: &TickJson) -> Self {
+        let before = base
+            .creatures
+            .creatures
+            .iter()
+            .map(|creature| (creature.id.as_str(), creature))
+            .collect::<HashMap<_, _>>();
+        let after = tick
+            .creatures
+            .creatures
+            .iter()
+            .map(|creature| creature.id.as_str())
+            .collect::<HashSet<_>>();
+
+        let mut changed = Vec::new();
+        let mut added = Vec::new();
+        for creature in tick.creatures.creatures.iter() {
+            match before.get(creature.id.as_str()) {
+                Some(previous) => changed.extend(creature.changed_since(previous)),
+                None => added.push(creature.clone()),
+            }
+        }
+
+        let removed = before
+            .keys()
+            .filter(|id| !after.contains(*id))
+            .map(|id| id.to_string())
+            .collect();
+
+        Self {
+            tick: tick.tick,
+            base: base.tick,
+            timestamp: tick.timestamp,
+            changed,
+            added,
+            removed,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn creature(id: &str, x: f64, y: f64, state: &str) -> CreatureJson {
+        CreatureJson {
+            id: id.to_string(),
+            url: format!("https://example.com/{}", id),
+            display_name: id.to_uppercase(),
+            radius: 1.0,
+            position: PositionJson { x, y },
+            state: Value::String(state.to_string()),
+            sprite_path: format!("/api/kennel-club/{}/img/idle/0", id),
+        }
+    }
+
+    fn tick(number: u64, creatures: Vec<CreatureJson>) -> TickJson {
+        TickJson {
+            schema: SCHEMA_VERSION,
+            tick: number,
+            timestamp: number * 1000,
+            interval_ms: 1000,
+            world: WorldJson {
+                width: 10.0,
+                height: 10.0,
+            },
+            creatures: KennelJson { creatures },
+            reload: None,
+        }
+    }
+
+    fn fields(value: Value) -> Map<String, Value> {
+        match value {
+            Value::Object(fields) => fields,
+            _ => panic!("not an object"),
+        }
+    }
+
+    #[test]
+    fn between_splits_creatures_into_added_changed_and_removed() {
+        let base = tick(
+            1,
+            vec![
+                creature("moved", 0.0, 0.0, "idle"),
+                creature("still", 1.0, 1.0, "idle"),
+                creature("gone", 2.0, 2.0, "idle"),
+            ],
+        );
+        let next = tick(
+            2,
+            vec![
+                creature("moved", 3.0, 0.0, "idle"),
+                creature("still", 1.0, 1.0, "idle"),
+                creature("new", 4.0, 4.0, "sleeping"),
+            ],
+        );
+
+        let delta = DeltaJson::between(&base, &next);
+
+        assert_eq!(delta.tick, 2);
+        assert_eq!(delta.base, 1);
+        assert_eq!(delta.timestamp, 2000);
+        assert_eq!(
+            delta.changed,
+            vec![fields(
+                json!({ "id": "moved", "position": { "x": 3.0, "y": 0.0 } })
+            )]
+        );
+        assert_eq!(
+            delta.added.iter().map(CreatureJson::id).collect::<Vec<_>>(),
+            vec!["new"]
+        );
+        assert_eq!(delta.removed, vec!["gone".to_string()]);
+    }
+
+    #[test]
+    fn between_identical_ticks_is_empty() {
+        let creatures = vec![
+            creature("a", 0.0, 0.0, "idle"),
+            creature("b", 1.0, 1.0, "idle"),
+        ];
+        let delta = DeltaJson::between(&tick(1, creatures.clone()), &tick(2, creatures));
+
+        assert!(delta.changed.is_empty());
+        assert!(delta.added.is_empty());
+        assert!(delta.removed.is_empty());
+    }
+
+    #[test]
+    fn changed_since_keeps_only_changed_fields_and_the_id() {
+        let before = creature("a", 0.0, 0.0, "idle");
+        let after = creature("a", 0.0, 0.0, "sleeping");
+
+        assert_eq!(
+            after.changed_since(&before),
+            Some(fields(json!({ "id": "a", "state": "sleeping" })))
+        );
+        assert_eq!(before.changed_since(&before.clone()), None);
+    }
+
+    #[test]
+    fn changed_since_sends_whole_nested_values() {
+        let before = creature("a", 0.0, 5.0, "idle");
+        let after = creature("a", 1.0, 5.0, "idle");
+
+        // a position is replaced as a whole, not merged field by field
+        assert_eq!(
+            after.changed_since(&before),
+            Some(fields(
+                json!({ "id": "a", "position": { "x": 1.0, "y": 5.0 } })
+            ))
+        );
+    }
+}
@@ -0,0 +1,94 @@
+use std::sync::{
+    Arc,
+    atomic::{AtomicBool, Ordering},
+};
+
+use kennel_club::Kennel;
+use rocket::tokio::{self, sync::watch};
+
+use crate::{
+    kennel::{config::Config, params::ImageParams, render},
+    metrics::Metrics,
+};
+
+// boundary between the parts of `multipart/x-mixed-replace`
+pub static LIVE_BOUNDARY: &str = "kennelframe";
+
+pub struct LiveFrame {
+    pub tick: u64,
+    pub data: Vec<u8>,
+}
+
+impl LiveFrame {
+    // one part of the multipart body, boundary and headers included
+    pub fn part(&self) -> Vec<u8> {
+        let header = format!(
+            "--{}\r\nContent-Type: image/jpeg\r\nContent-Length: {}\r\n\r\n",
+            LIVE_BOUNDARY,
+            self.data.len()
+        );
+
+        let mut part = Vec::with_capacity(header.len() + self.data.len() + 2);
+        part.extend_from_slice(header.as_bytes());
+        part.extend_from_slice(&self.data);
+        part.extend_from_slice(b"\r\n");
+        part
+    }
+}
+
+// one JPEG per tick shared by every viewer, and none at all without viewers
+pub struct Live {
+    params: ImageParams,
+    frames: watch::Sender<Option<Arc<LiveFrame>>>,
+    rendering: AtomicBool,
+    metrics: Arc<Metrics>,
+}
+
+impl Live {
+    pub fn new(config: &Config, metrics: Arc<Metrics>) -> Self {
+        Live {
+            params: ImageParams::jpeg(config.live_size, config.live_quality),
+            frames: watch::Sender::new(None),
+            rendering: AtomicBool::new(false),
+            metrics,
+        }
+    }
+
+    pub fn subscribe(&self) -> watch::Receiver<Option<Arc<LiveFrame>>> {
+        self.frames.subscribe()
+    }
+
+    pub fn is_stale(&self, tick: u64) -> bool {
+        self.frames
+            .borrow()
+            .as_ref()
+            .is_none_or(|frame| frame.tick != tick)
+    }
+
+    // a tick that comes in while the last one is still rendering is skipped,
+    // so slow renders never pile up
+    pub fn on_tick(self: &Arc<Self>, kennel: Arc<Kennel>, tick: u64) {
+        let viewers = self.frames.receiver_count();
+        self.metrics
+            .set_gauge("kennel_live_viewers", viewers as i64);
+        if viewers == 0 || self.rendering.swap(true, Ordering::AcqRel) {
+            return;
+        }
+
+        let live = self.clone();
+        tokio::spawn(async move {
+            match render::render_uncached(kennel, live.params.clone()).await {
+                Ok(data) => {
+                    live.frames
+                        .send_replace(Some(Arc::new(LiveFrame { tick, data })));
+                    live.metrics.increment("kennel_live_frames_total");
+                }
+                Err(message) => {
+                    log::warn!("Error rendering live kennel frame: {}", message);
+                    live.metrics.increment("kennel_live_render_failures_total");
+                }
+            }
+            live.rendering.store(false, Ordering::Release);
+        });
+    }
+}
@@ -0,0 +1,264 @@
+use std::{
+    fs,
+    ops::Deref,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use rocket::{
+    Request, catch,
+    futures::lock::Mutex,
+    http::Status,
+    request::{FromRequest, Outcome},
+    serde::json::Json,
+    tokio::{self, task, time::sleep},
+};
+use serde::Serialize;
+
+use crate::{
+    kennel::{State, config::Config, json::ReloadJson, state::StateHealth, tick::unix_millis},
+    metrics::Metrics,
+};
+
+#[derive(Serialize, Clone)]
+pub struct FileError {
+    path: String,
+    error: String,
+}
+
+// why the kennel isn't running, with whatever files we could pin it on
+#[derive(Serialize, Clone)]
+pub struct LoadError {
+    message: String,
+    files: Vec<FileError>,
+    failed_at: u64,
+}
+
+impl LoadError {
+    fn new(dir: &Path, message: String) -> Self {
+        LoadError {
+            message,
+            files: diagnose(dir),
+            failed_at: unix_millis(SystemTime::now()),
+        }
+    }
+}
+
+// checks the files we know how to read on our own, since `Kennel::load` only
+// says that something failed
+fn diagnose(dir: &Path) -> Vec<FileError> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            return vec![FileError {
+                path: dir.display().to_string(),
+                error: e.to_string(),
+            }];
+        }
+    };
+
+    let mut errors = Vec::new();
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            errors.extend(diagnose(&path));
+            continue;
+        }
+
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(|extension| extension.to_ascii_lowercase());
+        let result = match extension.as_deref() {
+            Some("png" | "gif" | "jpg" | "jpeg" | "webp" | "bmp") => {
+                image::open(&path).map(|_| ()).map_err(|e| e.to_string())
+            }
+            Some("json") => fs::read(&path).map_err(|e| e.to_string()).and_then(|data| {
+                serde_json::from_slice::<serde_json::Value>(&data)
+                    .map(|_| ())
+                    .map_err(|e| e.to_string())
+            }),
+            _ => Ok(()),
+        };
+
+        if let Err(error) = result {
+            errors.push(FileError {
+                path: path.display().to_string(),
+                error,
+            });
+        }
+    }
+
+    errors.sort_by(|a, b| a.path.cmp(&b.path));
+    errors
+}
+
+#[derive(Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum KennelHealth {
+    Available(StateHealth),
+    Unavailable { error: LoadError },
+}
+
+impl KennelHealth {
+    pub fn is_ok(&self) -> bool {
+        match self {
+            KennelHealth::Available(health) => health.is_ok(),
+            KennelHealth::Unavailable { .. } => false,
+        }
+    }
+}
+
+// the kennel, or why it couldn't be loaded
+pub struct Loader {
+    dir: PathBuf,
+    config: Config,
+    metrics: Arc<Metrics>,
+    state: Mutex<Result<Arc<State>, LoadError>>,
+    // one load or reload at a time
+    loading: Mutex<()>,
+}
+
+impl Loader {
+    // never fails, a kennel that won't load leaves the rest of the server up
+    pub fn start(dir: PathBuf, config: Config, metrics: Arc<Metrics>) -> Arc<Self> {
+        let state = State::load(&dir, &config, metrics.clone())
+            .map(Arc::new)
+            .map_err(|message| {
+                log::error!("Error loading kennel, starting without it: {}", message);
+                LoadError::new(&dir, message)
+            });
+        metrics.set_gauge("kennel_available", state.is_ok() as i64);
+
+        let retry = state.is_err();
+        let loader = Arc::new(Loader {
+            dir,
+            config,
+            metrics,
+            state: Mutex::new(state),
+            loading: Mutex::new(()),
+        });
+        if retry {
+            tokio::spawn(loader.clone().retry());
+        }
+
+        loader
+    }
+
+    pub async fn get(&self) -> Result<Arc<State>, LoadError> {
+        self.state.lock().await.clone()
+    }
+
+    async fn retry(self: Arc<Self>) {
+        let interval = Duration::from_millis(self.config.load_retry_ms);
+        while self.get().await.is_err() {
+            sleep(interval).await;
+            let _ = self.reload().await;
+        }
+    }
+
+    // reloads a running kennel, or makes another attempt at starting one
+    pub async fn reload(&self) -> Result<ReloadJson, String> {
+        if let Ok(state) = self.get().await {
+            return state.reload().await;
+        }
+
+        let _loading = self.loading.lock().await;
+        if let Ok(state) = self.get().await {
+            return Ok(state.roster().await);
+        }
+
+        let dir = self.dir.clone();
+        let config = self.config.clone();
+        let metrics = self.metrics.clone();
+        let loaded = task::spawn_blocking(move || {
+            State::load(&dir, &config, metrics).map_err(|message| LoadError::new(&dir, message))
+        })
+        .await
+        .map_err(|e| LoadError::new(&self.dir, e.to_string()))
+        .and_then(|result| result);
+
+        let mut state = self.state.lock().await;
+        match loaded {
+            Ok(loaded) => {
+                log::info!("Kennel is back online");
+                let loaded = Arc::new(loaded);
+                *state = Ok(loaded.clone());
+                self.metrics.set_gauge("kennel_available", 1);
+                Ok(loaded.roster().await)
+            }
+            Err(error) => {
+                log::warn!("Kennel is still unavailable: {}", error.message);
+                let message = error.message.clone();
+                *state = Err(error);
+                Err(message)
+            }
+        }
+    }
+
+    pub async fn health(&self) -> KennelHealth {
+        match self.get().await {
+            Ok(state) => KennelHealth::Available(state.health().await),
+            Err(error) => KennelHealth::Unavailable { error },
+        }
+    }
+
+    pub async fn shutdown(&self) {
+        if let Ok(state) = self.get().await {
+            state.shutdown().await;
+        }
+    }
+}
+
+// the running kennel, or a 503 explained by `unavailable_catcher`
+pub struct Available(pub Arc<State>);
+
+impl Deref for Available {
+    type Target = State;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Available {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let Some(loader) = request.rocket().state::<Arc<Loader>>() else {
+            return Outcome::Error((Status::InternalServerError, ()));
+        };
+
+        match loader.get().await {
+            Ok(state) => Outcome::Success(Available(state)),
+            Err(error) => {
+                request.local_cache(|| Some(error));
+                Outcome::Error((Status::ServiceUnavailable, ()))
+            }
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct UnavailableJson {
+    error: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    detail: Option<LoadError>,
+}
+
+#[catch(503)]
+pub fn unavailable_catcher(request: &Request<'_>) -> (Status, Json<UnavailableJson>) {
+    let detail = request.local_cache(|| None::<LoadError>).clone();
+    let error = if detail.is_some() {
+        "Kennel unavailable"
+    } else {
+        "Service unavailable"
+    };
+
+    (
+        Status::ServiceUnavailable,
+        Json(UnavailableJson { error, detail }),
+    )
+}
@@ -1,29 +1,94 @@
-use std::{path::PathBuf, sync::Arc};
+use std::{path::PathBuf, sync::Arc, time::Duration};
 
+pub use admin::admin_routes;
 use kennel_club::ImageFormat;
+pub use loader::{KennelHealth, Loader, unavailable_catcher};
 use rocket::{
     Route, State as RocketState,
     fairing::AdHoc,
-    futures::{SinkExt, StreamExt},
+    futures::StreamExt,
     get,
-    http::{self},
+    http::{self, Accept, ContentType},
+    response::stream::{ByteStream, Event, EventStream},
     routes,
+    tokio::time::sleep,
 };
 pub use state::State;
+use state::Subscription;
 use tokio_stream::wrappers::ReceiverStream;
-use ws::{Message, WebSocket};
+use ws::{Message, WebSocket, frame::CloseCode};
 
-use crate::kennel::{response::Response, stream::greedy_zip};
+use crate::{
+    connections::ConnectionPermit,
+    kennel::{
+        animate::{AnimationParams, AnimationQuery},
+        config::Config,
+        encoding::{Encoding, Protocol, WithProtocol},
+        events::LastEventId,
+        feed::FeedMode,
+        interpolate::{FrameTimer, Interpolator},
+        json::{RemovedJson, TickJson},
+        live::LIVE_BOUNDARY,
+        loader::Available,
+        params::{ImageParams, ImageQuery},
+        response::Response,
+        socket::Backpressure,
+        stats::to_csv,
+        stream::{Fairness, Merged, merge},
+        tick::NOTHING_TO_DRAW,
+    },
+    metrics::Metrics,
+};
 
+mod admin;
+mod animate;
+mod command;
+mod config;
+mod encoding;
+mod events;
+mod feed;
+mod files;
+mod heatmap;
+mod history;
+mod interpolate;
 mod json;
+mod live;
+mod loader;
+mod params;
+mod recording;
+mod reload;
+mod render;
 mod response;
+mod snapshot;
+mod socket;
 mod state;
+mod stats;
 mod stream;
+mod supervisor;
+mod tick;
+mod timelapse;
+
+static EVENTS_HEARTBEAT: Duration = Duration::from_secs(15);
+static HISTORY_LIMIT: usize = 300;
+static HEATMAP_SIZE: u32 = 512;
+static HEATMAP_MIN_SIZE: u32 = 128;
+static HEATMAP_MAX_SIZE: u32 = 1024;
 
-pub fn init_kennel() -> (Arc<State>, AdHoc) {
+pub fn init_kennel(metrics: Arc<Metrics>) -> (Arc<Loader>, AdHoc) {
     let dir = PathBuf::from("./kennel-club");
-    let kennel = State::load(&dir).expect("Error loading kennel");
-    let kennel = Arc::new(kennel);
+    let config =
+        Config::from_figment(&rocket::Config::figment()).expect("Error loading kennel config");
+    let kennel = Loader::start(dir.clone(), config.clone(), metrics);
+
+    if config.watch {
+        let debounce = Duration::from_millis(config.reload_debounce_ms);
+        if let Err(message) = reload::watch(dir.clone(), kennel.clone(), debounce) {
+            log::warn!("Not watching {}: {}", dir.display(), message);
+        }
+    }
+    if let Err(message) = reload::on_hangup(kennel.clone()) {
+        log::warn!("Not reloading on SIGHUP: {}", message);
+    }
 
     let kennel_clone = kennel.clone();
     let cleanup = AdHoc::on_shutdown("Kennel shutdown", |_| {
@@ -35,23 +100,380 @@ pub fn init_kennel() -> (Arc<State>, AdHoc) {
     (kennel, cleanup)
 }
 
-#[get("/")]
-async fn kennel_handler(kennel: &RocketState<Arc<State>>) -> Response {
-    Response::new_json(kennel.as_json().await)
+#[get("/?<envelope>")]
+async fn kennel_handler(envelope: bool, accept: Option<&Accept>, kennel: Available) -> Response {
+    let encoding = Encoding::from_accept(accept);
+    if envelope {
+        Response::new_encoded(kennel.as_tick_json().await, encoding)
+    } else {
+        Response::new_encoded(kennel.as_json().await, encoding)
+    }
+}
+
+// reconnecting clients resume from `Last-Event-ID`, either the `<instance>.<tick>`
+// id of the last event or a bare tick number
+#[get("/events?<envelope>&<deltas>")]
+async fn kennel_events_handler(
+    envelope: bool,
+    deltas: bool,
+    last_event_id: LastEventId,
+    permit: ConnectionPermit,
+    kennel: Available,
+) -> EventStream![] {
+    let kennel_state = kennel.0.clone();
+    let mode = FeedMode::from_query(envelope, deltas);
+    let Subscription { mut receiver, .. } = kennel_state.subscribe().await;
+    let mut feed = kennel_state
+        .feed(mode, Encoding::Json, last_event_id.0.as_deref())
+        .await;
+    let current = kennel_state.as_tick_json().await;
+
+    // the subscription is dropped by the tick loop once `receiver` goes away
+    let stream = EventStream! {
+        let _permit = permit;
+        if let Some(event) = events::event(&mut feed, current) {
+            yield event;
+        }
+
+        while let Some(mut json) = receiver.recv().await {
+            // only the newest queued tick matters to a lagging client
+            while let Ok(newer) = receiver.try_recv() {
+                if let Some(event) = events::reload(&json) {
+                    yield event;
+                }
+                json = newer;
+            }
+            if let Some(event) = events::reload(&json) {
+                yield event;
+            }
+            if let Some(event) = events::event(&mut feed, json) {
+                yield event;
+            }
+        }
+    };
+
+    stream.heartbeat(EVENTS_HEARTBEAT)
+}
+
+#[get("/<creature_id>/events")]
+async fn creature_events_handler(
+    creature_id: String,
+    permit: ConnectionPermit,
+    kennel: Available,
+) -> Result<EventStream![], Response> {
+    let kennel_state = kennel.0.clone();
+    let Some(current) = kennel_state
+        .as_tick_json()
+        .await
+        .creature(&creature_id)
+        .cloned()
+    else {
+        return Err(Response::new_err(
+            http::Status::NotFound,
+            &format!("{} not found", creature_id),
+        ));
+    };
+    let Subscription { mut receiver, .. } = kennel_state.subscribe().await;
+
+    let stream = EventStream! {
+        let _permit = permit;
+        if let Ok(data) = serde_json::to_string(&current) {
+            yield Event::data(data);
+        }
+
+        while let Some(mut json) = receiver.recv().await {
+            while let Ok(newer) = receiver.try_recv() {
+                json = newer;
+            }
+            let Some(creature) = json.creature(&creature_id) else {
+                yield Event::json(&RemovedJson::new(&creature_id, json.tick())).event("removed");
+                break;
+            };
+            if let Ok(data) = serde_json::to_string(creature) {
+                yield Event::data(data);
+            }
+        }
+    };
+
+    Ok(stream.heartbeat(EVENTS_HEARTBEAT))
+}
+
+#[get("/history?<since>&<limit>")]
+async fn history_handler(
+    since: Option<u64>,
+    limit: Option<usize>,
+    accept: Option<&Accept>,
+    kennel: Available,
+) -> Response {
+    let limit = limit.unwrap_or(HISTORY_LIMIT).min(HISTORY_LIMIT);
+    let history = kennel.history(since, limit).await;
+    Response::new_encoded(history, Encoding::from_accept(accept))
+}
+
+// ranked below `/<creature_id>/img` and friends, which only clash on
+// ids that aren't ticks anyway
+#[get("/at/<tick>", rank = 1)]
+async fn tick_handler(tick: u64, accept: Option<&Accept>, kennel: Available) -> Response {
+    match kennel.tick_json_at(tick).await {
+        Some(json) => Response::new_encoded(json, Encoding::from_accept(accept)),
+        None => Response::new_err(
+            http::Status::NotFound,
+            &format!("Tick {} is not in history", tick),
+        ),
+    }
+}
+
+// replayed ticks only have their JSON
+fn nothing_to_draw(kennel: &State) -> Option<Response> {
+    kennel
+        .is_replay()
+        .then(|| Response::new_err(http::Status::NotFound, NOTHING_TO_DRAW))
+}
+
+#[get("/at/<tick>/img?<query..>")]
+async fn tick_img_handler(
+    tick: u64,
+    query: ImageQuery<'_>,
+    accept: Option<&Accept>,
+    kennel: Available,
+) -> Response {
+    if let Some(response) = nothing_to_draw(&kennel) {
+        return response;
+    }
+    let params = match ImageParams::parse(query, accept) {
+        Ok(params) => params,
+        Err(message) => return Response::new_err(http::Status::BadRequest, &message),
+    };
+
+    match kennel.image_at(tick, &params).await {
+        Some(Ok(data)) => Response::new_image(data, params.format()),
+        Some(Err(message)) => Response::new_err(http::Status::InternalServerError, &message),
+        None => Response::new_err(
+            http::Status::NotFound,
+            &format!("Tick {} can no longer be rendered", tick),
+        ),
+    }
 }
 
-#[get("/img")]
-async fn kennel_img_handler(kennel: &RocketState<Arc<State>>) -> Response {
-    match kennel.as_image(ImageFormat::Png).await {
-        Ok(data) => Response::new_image(data, ImageFormat::Png),
+#[get("/img?<query..>")]
+async fn kennel_img_handler(
+    query: ImageQuery<'_>,
+    accept: Option<&Accept>,
+    kennel: Available,
+) -> Response {
+    if let Some(response) = nothing_to_draw(&kennel) {
+        return response;
+    }
+    let params = match ImageParams::parse(query, accept) {
+        Ok(params) => params,
+        Err(message) => return Response::new_err(http::Status::BadRequest, &message),
+    };
+
+    match kennel.as_image(&params).await {
+        Ok(data) => Response::new_image(data, params.format()),
         Err(message) => Response::new_err(http::Status::InternalServerError, &message),
     }
 }
 
+#[get("/img/animated?<query..>")]
+async fn kennel_animation_handler(query: AnimationQuery<'_>, kennel: Available) -> Response {
+    if let Some(response) = nothing_to_draw(&kennel) {
+        return response;
+    }
+    let params = match AnimationParams::parse(query, kennel.config()) {
+        Ok(params) => params,
+        Err(message) => return Response::new_err(http::Status::BadRequest, &message),
+    };
+
+    match kennel.animation(&params).await {
+        Some(Ok(data)) => Response::new_animated_image(data, params.format().content_type()),
+        Some(Err(message)) => Response::new_err(http::Status::InternalServerError, &message),
+        None => Response::new_err(http::Status::NotFound, "No recent ticks to animate"),
+    }
+}
+
+// for clients that can only show an image, held open like a socket
+#[get("/img/live?<fps>")]
+async fn kennel_live_handler(
+    fps: Option<u32>,
+    permit: ConnectionPermit,
+    kennel: Available,
+) -> Result<(ContentType, ByteStream![Vec<u8>]), Response> {
+    if let Some(response) = nothing_to_draw(&kennel) {
+        return Err(response);
+    }
+    let max_fps = kennel.config().live_max_fps.max(1);
+    let fps = fps.filter(|fps| *fps > 0).unwrap_or(max_fps).min(max_fps);
+    let min_gap = Duration::from_secs(1) / fps;
+    let mut frames = kennel.live_frames().await;
+
+    let stream = ByteStream! {
+        let _permit = permit;
+        loop {
+            let frame = frames.borrow_and_update().clone();
+            if let Some(frame) = frame {
+                yield frame.part();
+            }
+
+            // ticks that come in faster than this viewer's frame rate are skipped
+            sleep(min_gap).await;
+            if frames.changed().await.is_err() {
+                break;
+            }
+        }
+    };
+
+    let content_type =
+        ContentType::new("multipart", "x-mixed-replace").with_params(("boundary", LIVE_BOUNDARY));
+    Ok((content_type, stream))
+}
+
+// JSON unless `format=png`, which draws the grid over the kennel
+#[get("/heatmap?<creature>&<format>&<size>")]
+async fn heatmap_handler(
+    creature: Option<&str>,
+    format: Option<&str>,
+    size: Option<u32>,
+    accept: Option<&Accept>,
+    kennel: Available,
+) -> Response {
+    let not_found = || {
+        Response::new_err(
+            http::Status::NotFound,
+            &format!("No heatmap for {}", creature.unwrap_or_default()),
+        )
+    };
+
+    match format.map(str::to_ascii_lowercase).as_deref() {
+        None | Some("json") => match kennel.heatmap(creature).await {
+            Some(json) => Response::new_encoded(json, Encoding::from_accept(accept)),
+            None => not_found(),
+        },
+        Some("png") => {
+            if let Some(response) = nothing_to_draw(&kennel) {
+                return response;
+            }
+            let size = size
+                .unwrap_or(HEATMAP_SIZE)
+                .clamp(HEATMAP_MIN_SIZE, HEATMAP_MAX_SIZE);
+            match kennel.heatmap_image(creature, size).await {
+                Some(Ok(data)) => Response::new_image(data, ImageFormat::Png),
+                Some(Err(message)) => {
+                    Response::new_err(http::Status::InternalServerError, &message)
+                }
+                None => not_found(),
+            }
+        }
+        Some(format) => Response::new_err(
+            http::Status::BadRequest,
+            &format!("Unsupported heatmap format `{}`", format),
+        ),
+    }
+}
+
+// `format=csv` for a spreadsheet, JSON otherwise
+fn wants_csv(format: Option<&str>) -> Result<bool, Response> {
+    match format.map(str::to_ascii_lowercase).as_deref() {
+        None | Some("json") => Ok(false),
+        Some("csv") => Ok(true),
+        Some(format) => Err(Response::new_err(
+            http::Status::BadRequest,
+            &format!("Unsupported stats format `{}`", format),
+        )),
+    }
+}
+
+// ranked by `sort`, one of `distance`, `speed`, `transitions`, `idle` or
+// `tracked`, or else the name of a creature state some creature has been in
+#[get("/stats?<sort>&<order>&<limit>&<format>")]
+async fn leaderboard_handler(
+    sort: Option<&str>,
+    order: Option<&str>,
+    limit: Option<usize>,
+    format: Option<&str>,
+    accept: Option<&Accept>,
+    kennel: Available,
+) -> Response {
+    let csv = match wants_csv(format) {
+        Ok(csv) => csv,
+        Err(response) => return response,
+    };
+    let ascending = match order {
+        None | Some("desc") => false,
+        Some("asc") => true,
+        Some(order) => {
+            return Response::new_err(
+                http::Status::BadRequest,
+                &format!("Unsupported order `{}`", order),
+            );
+        }
+    };
+
+    let leaderboard = match kennel
+        .leaderboard(
+            sort.unwrap_or("distance"),
+            ascending,
+            limit.unwrap_or(usize::MAX),
+        )
+        .await
+    {
+        Ok(leaderboard) => leaderboard,
+        Err(message) => return Response::new_err(http::Status::BadRequest, &message),
+    };
+    if csv {
+        Response::new_csv(leaderboard.to_csv())
+    } else {
+        Response::new_encoded(leaderboard, Encoding::from_accept(accept))
+    }
+}
+
+#[get("/<creature_id>/stats?<format>")]
+async fn creature_stats_handler(
+    creature_id: &str,
+    format: Option<&str>,
+    accept: Option<&Accept>,
+    kennel: Available,
+) -> Response {
+    let csv = match wants_csv(format) {
+        Ok(csv) => csv,
+        Err(response) => return response,
+    };
+
+    match kennel.creature_stats(creature_id).await {
+        Some(stats) if csv => Response::new_csv(to_csv([(None, &stats)])),
+        Some(stats) => Response::new_encoded(stats, Encoding::from_accept(accept)),
+        None => Response::new_err(
+            http::Status::NotFound,
+            &format!("{} not found", creature_id),
+        ),
+    }
+}
+
+#[get("/timelapse")]
+async fn timelapse_index_handler(kennel: Available) -> Response {
+    Response::new_json(kennel.timelapses().await)
+}
+
+// ranked below `/<creature_id>/img` and friends like `/at/<tick>`
+#[get("/timelapse/<date>", rank = 1)]
+async fn timelapse_handler(date: &str, kennel: Available) -> Response {
+    match kennel.timelapse(date).await {
+        Some(data) => Response::new_cached_image(data, ImageFormat::Gif),
+        None => Response::new_err(
+            http::Status::NotFound,
+            &format!("No time-lapse for {}", date),
+        ),
+    }
+}
+
 #[get("/<creature_id>")]
-async fn creature_handler(creature_id: &str, kennel: &RocketState<Arc<State>>) -> Response {
+async fn creature_handler(
+    creature_id: &str,
+    accept: Option<&Accept>,
+    kennel: Available,
+) -> Response {
     match kennel.get_creature(creature_id).await {
-        Some(creature) => Response::new_json(creature),
+        Some(creature) => Response::new_encoded(creature, Encoding::from_accept(accept)),
         None => Response::new_err(
             http::Status::NotFound,
             &format!("{} not found", creature_id),
@@ -60,7 +482,7 @@ async fn creature_handler(creature_id: &str, kennel: &RocketState<Arc<State>>) -
 }
 
 #[get("/<creature_id>/img")]
-async fn creature_img_handler(creature_id: &str, kennel: &RocketState<Arc<State>>) -> Response {
+async fn creature_img_handler(creature_id: &str, kennel: Available) -> Response {
     let (bytes, format) = kennel
         .get_sprite(creature_id)
         .await
@@ -77,7 +499,7 @@ async fn creature_img_handler(creature_id: &str, kennel: &RocketState<Arc<State>
 }
 
 #[get("/<creature_id>/img/<sprite_state>/<frame>")]
-async fn creature_img_by_handler(creature_id: &str, sprite_state: &str, frame: usize, kennel: &RocketState<Arc<State>>) -> Response {
+async fn creature_img_by_handler(creature_id: &str, sprite_state: &str, frame: usize, kennel: Available) -> Response {
     let (bytes, format) = kennel
         .get_sprite_by(creature_id, sprite_state, &frame)
         .await
@@ -94,7 +516,7 @@ async fn creature_img_by_handler(creature_id: &str, sprite_state: &str, frame: u
 }
 
 #[get("/<creature_id>/site")]
-async fn creature_site_handler(creature_id: &str, kennel: &RocketState<Arc<State>>) -> Response {
+async fn creature_site_handler(creature_id: &str, kennel: Available) -> Response {
     match kennel.get_creature(creature_id).await {
         Some(creature) => Response::new_permanent_redirect(creature.url()),
         None => Response::new_err(
@@ -105,15 +527,15 @@ async fn creature_site_handler(creature_id: &str, kennel: &RocketState<Arc<State
 }
 
 #[get("/random")]
-async fn random_creature_handler(kennel: &RocketState<Arc<State>>) -> Response {
+async fn random_creature_handler(accept: Option<&Accept>, kennel: Available) -> Response {
     match kennel.get_random_creature().await {
-        Some(creature) => Response::new_json(creature),
+        Some(creature) => Response::new_encoded(creature, Encoding::from_accept(accept)),
         None => Response::new_err(http::Status::NotFound, "No creatures found"),
     }
 }
 
 #[get("/random/site")]
-async fn random_creature_site_handler(kennel: &RocketState<Arc<State>>) -> Response {
+async fn random_creature_site_handler(kennel: Available) -> Response {
     match kennel.get_random_creature().await {
         Some(creature) => Response::new_temporary_redirect(creature.url()),
         None => Response::new_err(http::Status::NotFound, "No creatures found"),
@@ -123,8 +545,20 @@ async fn random_creature_site_handler(kennel: &RocketState<Arc<State>>) -> Respo
 pub fn kennel_routes() -> Vec<Route> {
     routes![
         kennel_handler,
+        kennel_events_handler,
         kennel_img_handler,
+        kennel_animation_handler,
+        kennel_live_handler,
+        history_handler,
+        heatmap_handler,
+        leaderboard_handler,
+        creature_stats_handler,
+        timelapse_index_handler,
+        timelapse_handler,
+        tick_handler,
+        tick_img_handler,
         creature_handler,
+        creature_events_handler,
         creature_img_handler,
         creature_img_by_handler,
         creature_site_handler,
@@ -133,36 +567,233 @@ pub fn kennel_routes() -> Vec<Route> {
     ]
 }
 
-#[get("/")]
-fn ws_kennel_handler(ws: WebSocket, kennel: &RocketState<Arc<State>>) -> ws::Channel<'static> {
-    let kennel_state = kennel.inner().clone();
-    ws.channel(move |mut message_stream| {
+#[get("/?<envelope>&<deltas>&<resume>&<interpolate>")]
+fn ws_kennel_handler(
+    ws: WebSocket,
+    envelope: bool,
+    deltas: bool,
+    resume: Option<String>,
+    interpolate: Option<u32>,
+    protocol: Protocol,
+    permit: ConnectionPermit,
+    kennel: Available,
+    metrics: &RocketState<Arc<Metrics>>,
+) -> WithProtocol<ws::Channel<'static>> {
+    let kennel_state = kennel.0.clone();
+    let metrics = metrics.inner().clone();
+    let mode = FeedMode::from_query(envelope, deltas);
+    let encoding = protocol.0.unwrap_or(Encoding::Json);
+    let send_timeout = Duration::from_millis(kennel_state.config().ws_send_timeout_ms);
+    let policy = kennel_state.config().slow_consumer_policy;
+    // interpolated frames per second, off unless asked for
+    let frame_rate = interpolate
+        .filter(|hz| *hz > 0)
+        .map(|hz| hz.min(kennel_state.config().max_interpolation_hz.max(1)));
+    let channel = ws.channel(move |mut message_stream| {
         Box::pin(async move {
-            let (uuid, receiver) = kennel_state.subscribe().await;
-            let mut stream = greedy_zip(message_stream.by_ref(), ReceiverStream::new(receiver));
-
-            while let Some((message, kennel_json)) = stream.next().await {
-                match (message, kennel_json) {
-                    (Some(Ok(Message::Close(_))), _) | (Some(Err(_)), _) => break,
-                    (_, Some(json)) => {
-                        let (sender, _) = stream.get_mut();
-                        if let Ok(json_str) = serde_json::to_string(&json) {
-                            sender.send(Message::text(json_str)).await.unwrap();
+            let _permit = permit;
+            let Subscription {
+                id,
+                receiver,
+                overflowed,
+            } = kennel_state.subscribe().await;
+            let backpressure = Backpressure {
+                policy,
+                overflowed,
+                send_timeout,
+            };
+            let mut feed = kennel_state.feed(mode, encoding, resume.as_deref()).await;
+            let current = kennel_state.as_tick_json().await;
+
+            let mut interpolator = frame_rate.map(|_| Interpolator::new(current.clone()));
+            // started by every tick sent, stopped once frames catch up with it
+            let frames = FrameTimer::new(frame_rate);
+
+            // send the current state right away instead of waiting for a tick
+            let mut connected = true;
+            if let Some(data) = feed.next(current) {
+                let message = encoding.to_message(data);
+                connected =
+                    socket::send(&mut message_stream, message, send_timeout, &metrics).await;
+            }
+
+            // ticks always win over frames, and a chatty client can't starve
+            // either of them, nor they the client
+            let updates = merge(ReceiverStream::new(receiver), frames, Fairness::Biased);
+            let mut stream = merge(message_stream.by_ref(), updates, Fairness::RoundRobin);
+
+            'connection: while connected {
+                let Some(item) = stream.next().await else {
+                    break;
+                };
+
+                let (sender, updates) = stream.get_mut();
+                let (receiver_stream, frames) = updates.get_mut();
+                let outgoing = match item {
+                    Merged::First(Ok(Message::Close(_)) | Err(_)) => break,
+                    Merged::First(Ok(message)) => {
+                        command::handle(message, &mut feed, &kennel_state).await
+                    }
+                    Merged::Second(Merged::Second(())) => {
+                        let Some(interpolator) = interpolator.as_ref() else {
+                            continue;
+                        };
+                        if interpolator.is_caught_up() {
+                            frames.stop();
+                        }
+                        interpolator.frame(&feed).into_iter().collect()
+                    }
+                    Merged::Second(Merged::First(json)) => {
+                        let mut outgoing = Vec::new();
+                        // skip to the newest queued tick, the feed diffs against what was sent
+                        let skipped = |json: &TickJson| outgoing.extend(feed.reload_notice(json));
+                        let Some(json) = backpressure
+                            .next(sender, receiver_stream.as_mut(), json, &metrics, skipped)
+                            .await
+                        else {
+                            break;
+                        };
+                        outgoing.extend(feed.reload_notice(&json));
+                        // frames only move between ticks the client was sent
+                        let interpolated = interpolator.as_ref().map(|_| json.clone());
+                        let message = feed.next(json);
+                        if let (Some(interpolator), Some(json)) = (
+                            interpolator.as_mut(),
+                            interpolated.filter(|_| message.is_some()),
+                        ) {
+                            interpolator.push(json);
+                            frames.start(feed.every());
                         }
+                        outgoing.extend(message);
+                        outgoing
                     }
-                    (_, _) => {}
                 };
+
+                for data in outgoing {
+                    let message = encoding.to_message(data);
+                    if !socket::send(sender, message, send_timeout, &metrics).await {
+                        break 'connection;
+                    }
+                }
+            }
+
+            let (_, updates) = stream.get_mut();
+            let (receiver_stream, _) = updates.get_mut();
+            kennel_state.unsubscribe(&id).await;
+            receiver_stream.close();
+
+            Ok(())
+        })
+    });
+
+    WithProtocol {
+        inner: channel,
+        protocol: protocol.0,
+    }
+}
+
+#[get("/<creature_id>")]
+async fn ws_creature_handler(
+    ws: WebSocket,
+    creature_id: String,
+    protocol: Protocol,
+    permit: ConnectionPermit,
+    kennel: Available,
+    metrics: &RocketState<Arc<Metrics>>,
+) -> Result<WithProtocol<ws::Channel<'static>>, Response> {
+    let kennel_state = kennel.0.clone();
+    let Some(current) = kennel_state
+        .as_tick_json()
+        .await
+        .creature(&creature_id)
+        .cloned()
+    else {
+        return Err(Response::new_err(
+            http::Status::NotFound,
+            &format!("{} not found", creature_id),
+        ));
+    };
+
+    let metrics = metrics.inner().clone();
+    let encoding = protocol.0.unwrap_or(Encoding::Json);
+    let send_timeout = Duration::from_millis(kennel_state.config().ws_send_timeout_ms);
+    let policy = kennel_state.config().slow_consumer_policy;
+    let channel = ws.channel(move |mut message_stream| {
+        Box::pin(async move {
+            let _permit = permit;
+            let Subscription {
+                id,
+                receiver,
+                overflowed,
+            } = kennel_state.subscribe().await;
+            let backpressure = Backpressure {
+                policy,
+                overflowed,
+                send_timeout,
+            };
+
+            let mut connected = true;
+            if let Ok(data) = encoding.encode(&current) {
+                let message = encoding.to_message(data);
+                connected =
+                    socket::send(&mut message_stream, message, send_timeout, &metrics).await;
+            }
+
+            let mut stream = merge(
+                message_stream.by_ref(),
+                ReceiverStream::new(receiver),
+                Fairness::RoundRobin,
+            );
+
+            while connected {
+                let Some(item) = stream.next().await else {
+                    break;
+                };
+
+                let (sender, receiver_stream) = stream.get_mut();
+                let json = match item {
+                    Merged::First(Ok(Message::Close(_)) | Err(_)) => break,
+                    // this channel only talks, there are no commands to handle
+                    Merged::First(Ok(_)) => continue,
+                    Merged::Second(json) => json,
+                };
+                let Some(json) = backpressure
+                    .next(sender, receiver_stream.as_mut(), json, &metrics, |_| {})
+                    .await
+                else {
+                    break;
+                };
+
+                let Some(creature) = json.creature(&creature_id) else {
+                    if let Ok(data) = encoding.encode(&RemovedJson::new(&creature_id, json.tick()))
+                    {
+                        let message = encoding.to_message(data);
+                        socket::send(sender, message, send_timeout, &metrics).await;
+                    }
+                    let reason = "Creature left the kennel";
+                    socket::close(sender, CloseCode::Normal, reason, send_timeout, &metrics).await;
+                    break;
+                };
+                if let Ok(data) = encoding.encode(creature) {
+                    let message = encoding.to_message(data);
+                    connected = socket::send(sender, message, send_timeout, &metrics).await;
+                }
             }
 
             let (_, receiver_stream) = stream.get_mut();
-            kennel_state.unsubscribe(&uuid).await;
+            kennel_state.unsubscribe(&id).await;
             receiver_stream.close();
 
             Ok(())
         })
+    });
+
+    Ok(WithProtocol {
+        inner: channel,
+        protocol: protocol.0,
     })
 }
 
 pub fn ws_kennel_routes() -> Vec<Route> {
-    routes![ws_kennel_handler,]
+    routes![ws_kennel_handler, ws_creature_handler]
 }
@@ -0,0 +1,316 @@
+use std::io::Cursor;
+
+use image::{DynamicImage, RgbaImage, codecs::jpeg::JpegEncoder, imageops};
+use kennel_club::ImageFormat;
+use rocket::{
+    FromForm,
+    http::{Accept, MediaType},
+};
+use webp_animation::{
+    Encoder as WebPEncoder, EncoderOptions, EncodingConfig, EncodingType, LossyEncodingConfig,
+};
+
+use crate::kennel::{
+    encoding::negotiate,
+    render::{IMAGE_FORMAT, IMAGE_HEIGHT, IMAGE_WIDTH},
+};
+
+static ALLOWED_SIZES: [u32; 5] = [128, 256, 512, 1024, 2048];
+static DEFAULT_QUALITY: u8 = 80;
+// how long the only frame of a still WebP lasts, it just has to be non-zero
+static WEBP_FRAME_MS: i32 = 100;
+
+#[derive(FromForm)]
+pub struct ImageQuery<'r> {
+    width: Option<u32>,
+    height: Option<u32>,
+    format: Option<&'r str>,
+    quality: Option<u8>,
+    viewport: Option<&'r str>,
+}
+
+// a rectangle of the full-size kennel image, in pixels
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Viewport {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+}
+
+impl Viewport {
+    // `x,y,width,height` as fractions of the kennel, e.g. `0.25,0.25,0.5,0.5`
+    fn parse(value: &str) -> Result<Self, String> {
+        let parts = value
+            .split(',')
+            .map(|part| part.trim().parse::<f64>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| format!("Invalid viewport `{}`", value))?;
+
+        let [x, y, width, height] = parts[..] else {
+            return Err(format!("Invalid viewport `{}`", value));
+        };
+
+        let in_bounds = |start: f64, length: f64| {
+            (0.0..1.0).contains(&start) && length > 0.0 && start + length <= 1.0
+        };
+        if !in_bounds(x, width) || !in_bounds(y, height) {
+            return Err(format!("Viewport `{}` is outside the kennel", value));
+        }
+
+        let to_pixels = |fraction: f64, size: u32| (fraction * size as f64).round() as u32;
+        Ok(Viewport {
+            x: to_pixels(x, IMAGE_WIDTH),
+            y: to_pixels(y, IMAGE_HEIGHT),
+            width: to_pixels(width, IMAGE_WIDTH).max(1),
+            height: to_pixels(height, IMAGE_HEIGHT).max(1),
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct ImageParams {
+    width: u32,
+    height: u32,
+    format: ImageFormat,
+    // JPEG always has one, WebP is lossless without one
+    quality: Option<u8>,
+    viewport: Option<Viewport>,
+}
+
+impl Default for ImageParams {
+    fn default() -> Self {
+        ImageParams {
+            width: IMAGE_WIDTH,
+            height: IMAGE_HEIGHT,
+            format: IMAGE_FORMAT,
+            quality: None,
+            viewport: None,
+        }
+    }
+}
+
+// rounds up to the next allowed size, capped at the largest one
+fn snap_size(size: u32) -> u32 {
+    ALLOWED_SIZES
+        .iter()
+        .copied()
+        .find(|allowed| *allowed >= size)
+        .unwrap_or(ALLOWED_SIZES[ALLOWED_SIZES.len() - 1])
+}
+
+fn parse_format(format: &str) -> Result<ImageFormat, String> {
+    match format.to_ascii_lowercase().as_str() {
+        "png" => Ok(ImageFormat::Png),
+        "webp" => Ok(ImageFormat::WebP),
+        "jpeg" | "jpg" => Ok(ImageFormat::Jpeg),
+        _ => Err(format!("Unsupported image format `{}`", format)),
+    }
+}
+
+fn from_media_type(media_type: &MediaType) -> Option<ImageFormat> {
+    if *media_type == MediaType::PNG {
+        Some(ImageFormat::Png)
+    } else if *media_type == MediaType::WEBP {
+        Some(ImageFormat::WebP)
+    } else if *media_type == MediaType::JPEG {
+        Some(ImageFormat::Jpeg)
+    } else {
+        None
+    }
+}
+
+impl ImageParams {
+    pub fn parse(query: ImageQuery<'_>, accept: Option<&Accept>) -> Result<Self, String> {
+        let viewport = query.viewport.map(Viewport::parse).transpose()?;
+        let format = match query.format {
+            Some(format) => parse_format(format)?,
+            None => negotiate(accept, from_media_type).unwrap_or(IMAGE_FORMAT),
+        };
+
+        // a missing side follows the aspect ratio of what is being shown, then
+        // snaps like a given one does so the cache stays bounded
+        let (view_width, view_height) = viewport
+            .map(|v| (v.width, v.height))
+            .unwrap_or((IMAGE_WIDTH, IMAGE_HEIGHT));
+        let scale = |size: u32, from: u32, to: u32| {
+            ((size as u64 * to as u64) / from as u64).clamp(1, IMAGE_WIDTH.max(IMAGE_HEIGHT) as u64)
+                as u32
+        };
+        let (width, height) = match (query.width.map(snap_size), query.height.map(snap_size)) {
+            (Some(w), Some(h)) => (w, h),
+            (Some(w), None) => (w, snap_size(scale(w, view_width, view_height))),
+            (None, Some(h)) => (snap_size(scale(h, view_height, view_width)), h),
+            (None, None) => (IMAGE_WIDTH, IMAGE_HEIGHT),
+        };
+
+        // quality only applies to the lossy formats, asking for one makes WebP lossy
+        let quality = match (format, query.quality) {
+            (ImageFormat::Jpeg, quality) => Some(quality.unwrap_or(DEFAULT_QUALITY).clamp(1, 100)),
+            (ImageFormat::WebP, quality) => quality.map(|quality| quality.clamp(1, 100)),
+            _ => None,
+        };
+
+        Ok(ImageParams {
+            width,
+            height,
+            format,
+            quality,
+            viewport,
+        })
+    }
+
+    // a full view of the kennel as JPEG, `size` pixels wide
+    pub fn jpeg(size: u32, quality: u8) -> Self {
+        let width = snap_size(size);
+        ImageParams {
+            width,
+            height: (width as u64 * IMAGE_HEIGHT as u64 / IMAGE_WIDTH as u64).max(1) as u32,
+            format: ImageFormat::Jpeg,
+            quality: Some(quality.clamp(1, 100)),
+            viewport: None,
+        }
+    }
+
+    pub fn format(&self) -> ImageFormat {
+        self.format
+    }
+
+    // whether these params describe the frame the kennel renders itself
+    pub fn is_base(&self) -> bool {
+        *self == ImageParams::default()
+    }
+
+    pub fn apply(&self, base: &RgbaImage) -> Result<Vec<u8>, String> {
+        let view = match self.viewport {
+            Some(v) => imageops::crop_imm(base, v.x, v.y, v.width, v.height).to_image(),
+            None => base.clone(),
+        };
+        let image = if view.dimensions() == (self.width, self.height) {
+            view
+        } else {
+            imageops::resize(
+                &view,
+                self.width,
+                self.height,
+                imageops::FilterType::Triangle,
+            )
+        };
+
+        let mut out = Cursor::new(Vec::new());
+        match (self.format, self.quality) {
+            (ImageFormat::Jpeg, quality) => {
+                let quality = quality.unwrap_or(DEFAULT_QUALITY);
+                let rgb = DynamicImage::ImageRgba8(image).to_rgb8();
+                JpegEncoder::new_with_quality(&mut out, quality).encode_image(&rgb)
+            }
+            (ImageFormat::WebP, Some(quality)) => return encode_lossy_webp(&image, quality),
+            (format, _) => DynamicImage::ImageRgba8(image).write_to(&mut out, format),
+        }
+        .map_err(|e| e.to_string())?;
+
+        Ok(out.into_inner())
+    }
+}
+
+// the image crate only writes lossless WebP, libwebp does lossy
+fn encode_lossy_webp(image: &RgbaImage, quality: u8) -> Result<Vec<u8>, String> {
+    let options = EncoderOptions {
+        encoding_config: Some(EncodingConfig {
+            encoding_type: EncodingType::Lossy(LossyEncodingConfig::default()),
+            quality: quality as f32,
+            ..EncodingConfig::default()
+        }),
+        ..EncoderOptions::default()
+    };
+    let mut encoder =
+        WebPEncoder::new_with_options(image.dimensions(), options).map_err(|e| e.to_string())?;
+    encoder
+        .add_frame(image.as_raw(), 0)
+        .map_err(|e| e.to_string())?;
+    let data = encoder.finalize(WEBP_FRAME_MS).map_err(|e| e.to_string())?;
+    Ok(data.to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn query<'r>(
+        width: Option<u32>,
+        height: Option<u32>,
+        format: Option<&'r str>,
+        quality: Option<u8>,
+        viewport: Option<&'r str>,
+    ) -> ImageQuery<'r> {
+        ImageQuery {
+            width,
+            height,
+            format,
+            quality,
+            viewport,
+        }
+    }
+
+    fn size(width: Option<u32>, height: Option<u32>, viewport: Option<&str>) -> (u32, u32) {
+        let params = ImageParams::parse(query(width, height, None, None, viewport), None).unwrap();
+        (params.width, params.height)
+    }
+
+    fn quality(format: &str, quality: Option<u8>) -> Option<u8> {
+        let query = query(None, None, Some(format), quality, None);
+        ImageParams::parse(query, None).unwrap().quality
+    }
+
+    #[test]
+    fn sizes_snap_up_to_the_next_allowed_one() {
+        assert_eq!(size(Some(1), Some(128), None), (128, 128));
+        assert_eq!(size(Some(300), Some(512), None), (512, 512));
+        assert_eq!(size(Some(1025), Some(2000), None), (2048, 2048));
+        assert_eq!(size(Some(5000), Some(u32::MAX), None), (2048, 2048));
+    }
+
+    #[test]
+    fn a_missing_side_follows_the_view_and_snaps_too() {
+        assert_eq!(size(Some(300), None, None), (512, 512));
+        assert_eq!(size(None, Some(200), None), (256, 256));
+
+        // a 2048x512 strip of the kennel
+        let strip = Some("0,0,1,0.25");
+        assert_eq!(size(Some(1024), None, strip), (1024, 256));
+        // 64 pixels high is below the smallest size
+        assert_eq!(size(Some(256), None, strip), (256, 128));
+        assert_eq!(size(None, Some(256), strip), (1024, 256));
+    }
+
+    #[test]
+    fn no_parameters_is_the_base_image() {
+        let params = ImageParams::parse(query(None, None, None, None, None), None).unwrap();
+        assert!(params.is_base());
+
+        let params =
+            ImageParams::parse(query(Some(2048), None, Some("png"), None, None), None).unwrap();
+        assert!(params.is_base());
+    }
+
+    #[test]
+    fn quality_only_applies_to_lossy_formats() {
+        assert_eq!(quality("jpeg", None), Some(DEFAULT_QUALITY));
+        assert_eq!(quality("jpg", Some(0)), Some(1));
+        assert_eq!(quality("webp", None), None);
+        assert_eq!(quality("webp", Some(150)), Some(100));
+        assert_eq!(quality("png", Some(50)), None);
+    }
+
+    #[test]
+    fn rejects_unknown_formats_and_bad_viewports() {
+        let parse =
+            |format, viewport| ImageParams::parse(query(None, None, format, None, viewport), None);
+
+        assert!(parse(Some("gif"), None).is_err());
+        assert!(parse(None, Some("0,0,1")).is_err());
+        assert!(parse(None, Some("0.5,0,0.6,1")).is_err());
+        assert!(parse(None, Some("0,0,0,1")).is_err());
+        assert!(parse(None, Some("0.25,0.25,0.5,0.5")).is_ok());
+    }
+}
@@ -0,0 +1,432 @@
+use std::{
+    fs,
+    io::{BufReader, ErrorKind, Read},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use rocket::tokio::{
+    self,
+    fs::{File, OpenOptions},
+    io::AsyncWriteExt,
+    sync::mpsc::{self, Sender},
+    task,
+};
+
+use crate::{
+    kennel::{config::Config, json::TickJson, tick::unix_millis},
+    metrics::Metrics,
+};
+
+// every recording file starts with this, followed by length-prefixed ticks
+static MAGIC: &[u8] = b"KREC\x02";
+static RECORDING_PREFIX: &str = "recording-";
+static RECORDING_EXTENSION: &str = "krec";
+static RECORDER_QUEUE_SIZE: usize = 64;
+
+fn is_recording(path: &Path) -> bool {
+    let name = path.file_name().and_then(|name| name.to_str());
+    name.is_some_and(|name| name.starts_with(RECORDING_PREFIX))
+        && path.extension().is_some_and(|e| e == RECORDING_EXTENSION)
+}
+
+// oldest first, file names sort by when they were started
+fn list(dir: &Path) -> Vec<PathBuf> {
+    let mut paths = fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .flatten()
+                .map(|entry| entry.path())
+                .filter(|path| is_recording(path))
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    paths.sort();
+    paths
+}
+
+// blocking, removes all but the newest `keep` recordings
+fn prune(dir: &Path, keep: usize) {
+    let stale = list(dir);
+    for path in stale.iter().take(stale.len().saturating_sub(keep.max(1))) {
+        if let Err(e) = fs::remove_file(path) {
+            log::warn!("Error removing recording {}: {}", path.display(), e);
+        }
+    }
+}
+
+struct Writer {
+    dir: PathBuf,
+    max_bytes: u64,
+    keep: usize,
+    file: Option<(File, u64)>,
+    // tells apart files started within the same millisecond
+    sequence: u64,
+}
+
+impl Writer {
+    async fn rotate(&mut self) -> Result<(), String> {
+        tokio::fs::create_dir_all(&self.dir)
+            .await
+            .map_err(|e| e.to_string())?;
+        let name = format!(
+            "{}{:020}-{:06}.{}",
+            RECORDING_PREFIX,
+            unix_millis(SystemTime::now()),
+            self.sequence,
+            RECORDING_EXTENSION
+        );
+        self.sequence += 1;
+        // never truncate a recording that is already there
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(self.dir.join(name))
+            .await
+            .map_err(|e| e.to_string())?;
+        file.write_all(MAGIC).await.map_err(|e| e.to_string())?;
+        self.file = Some((file, MAGIC.len() as u64));
+
+        let (dir, keep) = (self.dir.clone(), self.keep);
+        task::spawn_blocking(move || prune(&dir, keep))
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    async fn write(&mut self, tick: &TickJson) -> Result<(), String> {
+        // named fields, as ticks leave out what they don't have
+        let data = rmp_serde::to_vec_named(tick).map_err(|e| e.to_string())?;
+        let size = 4 + data.len() as u64;
+        if self
+            .file
+            .as_ref()
+            .is_none_or(|(_, written)| written + size > self.max_bytes)
+        {
+            self.rotate().await?;
+        }
+
+        let (file, written) = self.file.as_mut().expect("Recording file is open");
+        file.write_all(&(data.len() as u32).to_le_bytes())
+            .await
+            .map_err(|e| e.to_string())?;
+        file.write_all(&data).await.map_err(|e| e.to_string())?;
+        file.flush().await.map_err(|e| e.to_string())?;
+        *written += size;
+        Ok(())
+    }
+}
+
+// appends every tick sent to the returned queue to files under `record_dir`
+pub fn start(config: &Config, metrics: Arc<Metrics>) -> Sender<TickJson> {
+    let (tx, mut rx) = mpsc::channel::<TickJson>(RECORDER_QUEUE_SIZE);
+    let mut writer = Writer {
+        dir: config.record_dir.clone(),
+        max_bytes: config.record_max_bytes,
+        keep: config.record_keep,
+        file: None,
+        sequence: 0,
+    };
+
+    tokio::spawn(async move {
+        while let Some(tick) = rx.recv().await {
+            if let Err(message) = writer.write(&tick).await {
+                log::warn!("Error writing kennel recording: {}", message);
+                metrics.increment("kennel_record_errors_total");
+                // start over in a fresh file rather than appending to a broken one
+                writer.file = None;
+            }
+        }
+    });
+
+    tx
+}
+
+// blocking, a recording with its header already read
+fn open(path: &Path) -> Result<BufReader<fs::File>, String> {
+    let mut file = BufReader::new(fs::File::open(path).map_err(|e| e.to_string())?);
+    let mut magic = vec![0; MAGIC.len()];
+    match file.read_exact(&mut magic) {
+        Ok(()) if magic == MAGIC => Ok(file),
+        Ok(()) => Err(format!("{} is not a kennel recording", path.display())),
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => {
+            Err(format!("{} is not a kennel recording", path.display()))
+        }
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+// blocking, `None` at the end of `path`; a crash mid-write leaves a truncated
+// last record, which is skipped
+fn read_record(file: &mut impl Read, path: &Path) -> Result<Option<TickJson>, String> {
+    let mut length = [0; 4];
+    match file.read_exact(&mut length) {
+        Ok(()) => {}
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e.to_string()),
+    }
+
+    let length = u32::from_le_bytes(length) as usize;
+    let mut record = Vec::new();
+    file.by_ref()
+        .take(length as u64)
+        .read_to_end(&mut record)
+        .map_err(|e| e.to_string())?;
+    if record.len() < length {
+        log::warn!("Ignoring truncated record at the end of {}", path.display());
+        return Ok(None);
+    }
+
+    rmp_serde::from_slice(&record)
+        .map(Some)
+        .map_err(|e| e.to_string())
+}
+
+// reads the records of one file at a time, from the first file again if looping
+struct Reader {
+    files: Vec<PathBuf>,
+    // into `files`, the one `file` reads
+    index: usize,
+    file: Option<BufReader<fs::File>>,
+    looping: bool,
+    // whether this pass over `files` found anything, so an empty one doesn't spin
+    found: bool,
+}
+
+impl Reader {
+    // blocking
+    fn next(&mut self) -> Result<Option<TickJson>, String> {
+        loop {
+            if let Some(file) = self.file.as_mut() {
+                if let Some(record) = read_record(file, &self.files[self.index])? {
+                    self.found = true;
+                    return Ok(Some(record));
+                }
+                self.file = None;
+                self.index += 1;
+            }
+
+            if self.index >= self.files.len() {
+                if !self.looping || !self.found {
+                    return Ok(None);
+                }
+                self.index = 0;
+                self.found = false;
+            }
+            self.file = Some(open(&self.files[self.index])?);
+        }
+    }
+}
+
+// plays recorded ticks back in order, from the start again if looping
+pub struct Replay {
+    first: TickJson,
+    // read ahead, so `pace` knows how long until it's due
+    upcoming: Option<TickJson>,
+    // only `None` while reading
+    reader: Option<Reader>,
+    speed: f64,
+}
+
+impl Replay {
+    // `path` is a single recording, or a directory of them played in order,
+    // blocking as it reads the first records
+    pub fn load(path: &Path, looping: bool, speed: f64) -> Result<Self, String> {
+        let files = if path.is_dir() {
+            list(path)
+        } else {
+            vec![path.to_path_buf()]
+        };
+
+        let count = files.len();
+        let mut reader = Reader {
+            files,
+            index: 0,
+            file: None,
+            looping,
+            found: false,
+        };
+        let Some(first) = reader.next()? else {
+            return Err(format!("No records to replay in {}", path.display()));
+        };
+        // the first record is the starting state
+        let upcoming = reader.next()?;
+
+        log::info!("Replaying {} recordings from {}", count, path.display());
+        Ok(Replay {
+            first,
+            upcoming,
+            reader: Some(reader),
+            speed: speed.max(0.01),
+        })
+    }
+
+    pub fn first(&self) -> &TickJson {
+        &self.first
+    }
+
+    // how long the recording waited before the next tick, at `speed` times
+    // real time
+    pub fn pace(&self) -> Duration {
+        let upcoming = self.upcoming.as_ref().unwrap_or(&self.first);
+        upcoming.interval().div_f64(self.speed)
+    }
+
+    // the reading happens off the executor, a record ahead of playback
+    pub async fn next(&mut self) -> Option<TickJson> {
+        let tick = self.upcoming.take()?;
+        let Some(mut reader) = self.reader.take() else {
+            return Some(tick);
+        };
+
+        let read = task::spawn_blocking(move || {
+            let upcoming = reader.next();
+            (reader, upcoming)
+        })
+        .await;
+        match read {
+            Ok((reader, Ok(upcoming))) => {
+                self.reader = Some(reader);
+                self.upcoming = upcoming;
+            }
+            // the replay ends after this tick
+            Ok((_, Err(message))) => log::warn!("Error reading recording: {}", message),
+            Err(e) => log::warn!("Error reading recording: {}", e),
+        }
+        Some(tick)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use uuid::Uuid;
+
+    use super::*;
+
+    fn tick(number: u64) -> TickJson {
+        serde_json::from_value(json!({
+            "schema": 1,
+            "tick": number,
+            "timestamp": number * 1000,
+            "interval_ms": 1000,
+            "world": { "width": 10.0, "height": 10.0 },
+            "creatures": [],
+        }))
+        .unwrap()
+    }
+
+    // length-prefixed like the writer does it
+    fn record(number: u64) -> Vec<u8> {
+        let data = rmp_serde::to_vec_named(&tick(number)).unwrap();
+        let mut record = (data.len() as u32).to_le_bytes().to_vec();
+        record.extend(data);
+        record
+    }
+
+    fn read_all(mut data: &[u8]) -> Result<Vec<u64>, String> {
+        let mut ticks = Vec::new();
+        while let Some(tick) = read_record(&mut data, Path::new("test.krec"))? {
+            ticks.push(tick.tick());
+        }
+        Ok(ticks)
+    }
+
+    fn write_recording(dir: &Path, name: &str, ticks: &[u64]) {
+        let mut data = MAGIC.to_vec();
+        for number in ticks {
+            data.extend(record(*number));
+        }
+        fs::write(dir.join(name), data).unwrap();
+    }
+
+    fn temp_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("kennel-recording-{}", Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn reads_records_until_the_end() {
+        let data = [record(0), record(1), record(2)].concat();
+        assert_eq!(read_all(&data), Ok(vec![0, 1, 2]));
+        assert_eq!(read_all(&[]), Ok(vec![]));
+    }
+
+    #[test]
+    fn skips_a_truncated_last_record() {
+        let last = record(2);
+        for cut in 1..last.len() {
+            let data = [record(0), record(1), last[..cut].to_vec()].concat();
+            assert_eq!(read_all(&data), Ok(vec![0, 1]), "cut at {}", cut);
+        }
+    }
+
+    #[test]
+    fn fails_on_a_record_that_doesnt_decode() {
+        let data = [record(0), vec![3, 0, 0, 0, 0xc1, 0xc1, 0xc1]].concat();
+        assert!(read_all(&data).is_err());
+    }
+
+    #[test]
+    fn replays_files_in_order_and_loops_back_to_the_first() {
+        let dir = temp_dir();
+        write_recording(&dir, "recording-1.krec", &[0, 1]);
+        write_recording(&dir, "recording-2.krec", &[2]);
+        fs::write(dir.join("notes.txt"), "not a recording").unwrap();
+
+        let mut reader = Reader {
+            files: list(&dir),
+            index: 0,
+            file: None,
+            looping: true,
+            found: false,
+        };
+        let ticks = (0..7)
+            .map(|_| reader.next().unwrap().map(|tick| tick.tick()))
+            .collect::<Vec<_>>();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            ticks,
+            vec![
+                Some(0),
+                Some(1),
+                Some(2),
+                Some(0),
+                Some(1),
+                Some(2),
+                Some(0)
+            ]
+        );
+    }
+
+    #[test]
+    fn looping_over_nothing_ends_instead_of_spinning() {
+        let dir = temp_dir();
+        write_recording(&dir, "recording-1.krec", &[]);
+
+        let mut reader = Reader {
+            files: list(&dir),
+            index: 0,
+            file: None,
+            looping: true,
+            found: false,
+        };
+        let next = reader.next();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(matches!(next, Ok(None)));
+    }
+
+    #[test]
+    fn rejects_files_that_arent_recordings() {
+        let dir = temp_dir();
+        fs::write(dir.join("recording-1.krec"), b"KREC\x01").unwrap();
+
+        let loaded = Replay::load(&dir, false, 1.0);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(loaded.is_err());
+    }
+}
@@ -0,0 +1,71 @@
+use std::{path::PathBuf, sync::Arc, time::Duration};
+
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use rocket::tokio::{
+    self,
+    sync::mpsc::{self, Receiver},
+    time::sleep,
+};
+
+use crate::kennel::loader::Loader;
+
+async fn reload(kennel: &Loader, reason: &str) {
+    match kennel.reload().await {
+        Ok(_) => log::info!("Reloaded kennel after {}", reason),
+        Err(message) => log::warn!("Kept running kennel after {}: {}", reason, message),
+    }
+}
+
+// editors touch a file several times per save, so wait for things to settle
+async fn debounce(mut changes: Receiver<()>, kennel: Arc<Loader>, debounce: Duration) {
+    while changes.recv().await.is_some() {
+        sleep(debounce).await;
+        while changes.try_recv().is_ok() {}
+        reload(&kennel, "a change to the data directory").await;
+    }
+}
+
+// reloads `kennel` whenever something under `dir` changes
+pub fn watch(dir: PathBuf, kennel: Arc<Loader>, debounce_for: Duration) -> Result<(), String> {
+    let (tx, rx) = mpsc::channel(1);
+    let mut watcher = RecommendedWatcher::new(
+        move |event: notify::Result<notify::Event>| {
+            if event.is_ok_and(|event| !event.kind.is_access()) {
+                // a reload is already due if the queue is full
+                let _ = tx.try_send(());
+            }
+        },
+        notify::Config::default(),
+    )
+    .map_err(|e| e.to_string())?;
+    watcher
+        .watch(&dir, RecursiveMode::Recursive)
+        .map_err(|e| e.to_string())?;
+
+    tokio::spawn(async move {
+        // the watcher stops once dropped
+        let _watcher = watcher;
+        debounce(rx, kennel, debounce_for).await;
+    });
+
+    Ok(())
+}
+
+#[cfg(unix)]
+pub fn on_hangup(kennel: Arc<Loader>) -> Result<(), String> {
+    use rocket::tokio::signal::unix::{SignalKind, signal};
+
+    let mut hangups = signal(SignalKind::hangup()).map_err(|e| e.to_string())?;
+    tokio::spawn(async move {
+        while hangups.recv().await.is_some() {
+            reload(&kennel, "SIGHUP").await;
+        }
+    });
+
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn on_hangup(_kennel: Arc<Loader>) -> Result<(), String> {
+    Ok(())
+}
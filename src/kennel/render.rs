@@ -0,0 +1,332 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Arc,
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+use image::RgbaImage;
+use kennel_club::{ImageFormat, Kennel};
+use rocket::{
+    futures::lock::Mutex,
+    tokio::{self, task},
+};
+use serde::Serialize;
+
+use crate::{kennel::params::ImageParams, metrics::Metrics};
+
+pub static IMAGE_WIDTH: u32 = 2048;
+pub static IMAGE_HEIGHT: u32 = 2048;
+pub static IMAGE_FORMAT: ImageFormat = ImageFormat::Png;
+
+// upper bound on the non-base images kept per tick
+static MAX_VARIANTS: usize = 32;
+// images of past ticks kept around, one per tick and params
+static MAX_PAST_IMAGES: usize = 16;
+
+struct Frame {
+    generation: u64,
+    base: Result<Vec<u8>, String>,
+    pixels: Option<Arc<RgbaImage>>,
+    variants: HashMap<ImageParams, Result<Vec<u8>, String>>,
+}
+
+impl Frame {
+    fn new(generation: u64, base: Result<Vec<u8>, String>) -> Self {
+        Frame {
+            generation,
+            base,
+            pixels: None,
+            variants: HashMap::new(),
+        }
+    }
+}
+
+// the newest tick waiting for an eager render, and whether one is running
+#[derive(Default)]
+struct Pending {
+    next: Option<(Arc<Kennel>, u64)>,
+    rendering: bool,
+}
+
+#[derive(Serialize, Clone, Default)]
+pub struct RenderHealth {
+    eager: bool,
+    last_rendered_at: Option<u64>,
+    last_render_ms: Option<u128>,
+    last_error: Option<String>,
+    failures: u64,
+}
+
+impl RenderHealth {
+    pub fn is_ok(&self) -> bool {
+        self.last_error.is_none()
+    }
+}
+
+pub fn render(kennel: &Kennel) -> Result<Vec<u8>, String> {
+    kennel.get_image(IMAGE_WIDTH, IMAGE_HEIGHT, IMAGE_FORMAT)
+}
+
+fn decode(data: &[u8]) -> Result<RgbaImage, String> {
+    image::load_from_memory_with_format(data, IMAGE_FORMAT)
+        .map(|image| image.to_rgba8())
+        .map_err(|e| e.to_string())
+}
+
+fn unix_secs(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+// for one-off images that aren't worth keeping around
+pub async fn render_uncached(kennel: Arc<Kennel>, params: ImageParams) -> Result<Vec<u8>, String> {
+    task::spawn_blocking(move || {
+        let base = render(&kennel)?;
+        if params.is_base() {
+            return Ok(base);
+        }
+        params.apply(&decode(&base)?)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+pub struct Renderer {
+    eager: bool,
+    frame: Mutex<Option<Frame>>,
+    rendering: Mutex<()>,
+    pending: Mutex<Pending>,
+    health: Mutex<RenderHealth>,
+    metrics: Arc<Metrics>,
+}
+
+impl Renderer {
+    pub fn new(eager: bool, metrics: Arc<Metrics>) -> Self {
+        Renderer {
+            eager,
+            frame: Mutex::new(None),
+            rendering: Mutex::new(()),
+            pending: Mutex::new(Pending::default()),
+            health: Mutex::new(RenderHealth {
+                eager,
+                ..RenderHealth::default()
+            }),
+            metrics,
+        }
+    }
+
+    // called by the tick loop once `kennel` is the current state
+    pub async fn on_tick(self: &Arc<Self>, kennel: Arc<Kennel>, generation: u64) {
+        // lazy frames are replaced by the first request for a newer tick
+        if !self.eager {
+            return;
+        }
+
+        // only one render runs at a time, ticks that come in meanwhile
+        // replace each other and the newest is rendered once it's done
+        let mut pending = self.pending.lock().await;
+        pending.next = Some((kennel, generation));
+        if pending.rendering {
+            return;
+        }
+        pending.rendering = true;
+        drop(pending);
+
+        // the previous frame keeps being served until this one is ready
+        let renderer = self.clone();
+        tokio::spawn(async move {
+            loop {
+                let mut pending = renderer.pending.lock().await;
+                let Some((kennel, generation)) = pending.next.take() else {
+                    pending.rendering = false;
+                    return;
+                };
+                drop(pending);
+
+                renderer.render_eagerly(kennel, generation).await;
+            }
+        });
+    }
+
+    async fn render_eagerly(&self, kennel: Arc<Kennel>, generation: u64) {
+        let started = Instant::now();
+        let result = task::spawn_blocking(move || render(&kennel))
+            .await
+            .map_err(|e| e.to_string())
+            .and_then(|result| result);
+
+        match result {
+            Ok(data) => {
+                let mut frame = self.frame.lock().await;
+                if frame.as_ref().is_none_or(|f| f.generation < generation) {
+                    *frame = Some(Frame::new(generation, Ok(data)));
+                }
+                drop(frame);
+                self.record_success(started).await;
+            }
+            Err(message) => self.record_failure(message).await,
+        }
+    }
+
+    // eager frames are kept until the next one is ready, lazy ones only serve
+    // the tick they were rendered for
+    fn is_missing(&self, frame: &Option<Frame>, generation: u64) -> bool {
+        frame
+            .as_ref()
+            .is_none_or(|f| !self.eager && f.generation < generation)
+    }
+
+    // lazy mode, or eager mode before the first frame is ready, rendered off
+    // the executor one at a time
+    async fn render_missing(&self, kennel: Arc<Kennel>, generation: u64) {
+        let _rendering = self.rendering.lock().await;
+        if !self.is_missing(&*self.frame.lock().await, generation) {
+            return;
+        }
+
+        let started = Instant::now();
+        let base = task::spawn_blocking(move || render(&kennel))
+            .await
+            .map_err(|e| e.to_string())
+            .and_then(|result| result);
+        match &base {
+            Ok(_) => self.record_success(started).await,
+            Err(message) => self.record_failure(message.clone()).await,
+        }
+
+        let mut frame = self.frame.lock().await;
+        if frame.as_ref().is_none_or(|f| f.generation < generation) {
+            *frame = Some(Frame::new(generation, base));
+        }
+    }
+
+    pub async fn get(
+        &self,
+        kennel: Arc<Kennel>,
+        generation: u64,
+        params: &ImageParams,
+    ) -> Result<Vec<u8>, String> {
+        if self.is_missing(&*self.frame.lock().await, generation) {
+            self.render_missing(kennel, generation).await;
+        }
+
+        // the lock is only held to look up and store, never while encoding
+        let (frame_generation, base, pixels) = {
+            let frame = self.frame.lock().await;
+            let Some(frame) = frame.as_ref() else {
+                return Err("Kennel frame was cleared while rendering".to_string());
+            };
+            if params.is_base() {
+                return frame.base.clone();
+            }
+            if let Some(data) = frame.variants.get(params) {
+                return data.clone();
+            }
+            (frame.generation, frame.base.clone()?, frame.pixels.clone())
+        };
+
+        let variant_params = params.clone();
+        let (pixels, data) = task::spawn_blocking(move || {
+            let pixels = match pixels {
+                Some(pixels) => pixels,
+                None => Arc::new(decode(&base)?),
+            };
+            let data = variant_params.apply(&pixels);
+            Ok::<_, String>((pixels, data))
+        })
+        .await
+        .map_err(|e| e.to_string())??;
+
+        let mut frame = self.frame.lock().await;
+        if let Some(frame) = frame
+            .as_mut()
+            .filter(|frame| frame.generation == frame_generation)
+        {
+            frame.pixels.get_or_insert(pixels);
+            if frame.variants.len() < MAX_VARIANTS {
+                frame.variants.insert(params.clone(), data.clone());
+            }
+        }
+        data
+    }
+
+    // the next request renders from scratch
+    pub async fn clear(&self) {
+        let mut frame = self.frame.lock().await;
+        frame.take();
+    }
+
+    pub async fn health(&self) -> RenderHealth {
+        self.health.lock().await.clone()
+    }
+
+    async fn record_success(&self, started: Instant) {
+        let elapsed = started.elapsed();
+        self.metrics.increment("kennel_renders_total");
+        self.metrics
+            .set_gauge("kennel_render_duration_ms", elapsed.as_millis() as i64);
+
+        let mut health = self.health.lock().await;
+        health.last_rendered_at = Some(unix_secs(SystemTime::now()));
+        health.last_render_ms = Some(elapsed.as_millis());
+        health.last_error = None;
+    }
+
+    async fn record_failure(&self, message: String) {
+        log::warn!("Error rendering kennel image: {}", message);
+        self.metrics.increment("kennel_render_failures_total");
+
+        let mut health = self.health.lock().await;
+        health.last_error = Some(message);
+        health.failures += 1;
+    }
+}
+
+// images of ticks other than the current one, rendered one at a time so a
+// client walking through history can't start a render per request
+#[derive(Default)]
+pub struct PastImages {
+    cache: Mutex<VecDeque<((u64, ImageParams), Vec<u8>)>>,
+    rendering: Mutex<()>,
+}
+
+impl PastImages {
+    async fn cached(&self, key: &(u64, ImageParams)) -> Option<Vec<u8>> {
+        let cache = self.cache.lock().await;
+        cache
+            .iter()
+            .find(|(cached, _)| cached == key)
+            .map(|(_, data)| data.clone())
+    }
+
+    pub async fn get(
+        &self,
+        kennel: Arc<Kennel>,
+        tick: u64,
+        params: &ImageParams,
+    ) -> Result<Vec<u8>, String> {
+        let key = (tick, params.clone());
+        if let Some(data) = self.cached(&key).await {
+            return Ok(data);
+        }
+
+        let _rendering = self.rendering.lock().await;
+        if let Some(data) = self.cached(&key).await {
+            return Ok(data);
+        }
+
+        let data = render_uncached(kennel, params.clone()).await?;
+
+        let mut cache = self.cache.lock().await;
+        if cache.len() >= MAX_PAST_IMAGES {
+            cache.pop_front();
+        }
+        cache.push_back((key, data.clone()));
+        Ok(data)
+    }
+
+    pub async fn clear(&self) {
+        self.cache.lock().await.clear();
+    }
+}
@@ -5,11 +5,17 @@ use rocket::{
 };
 use serde::Serialize;
 
+use crate::kennel::encoding::Encoding;
+
 #[derive(Responder)]
 pub enum Response {
     #[response(status = 200)]
     Json(String, ContentType, Header<'static>),
     #[response(status = 200)]
+    Text(String, ContentType, Header<'static>),
+    #[response(status = 200)]
+    Encoded(Vec<u8>, ContentType, Header<'static>),
+    #[response(status = 200)]
     Image(Vec<u8>, ContentType, Header<'static>),
     #[response(status = 200)]
     CachedImage(Vec<u8>, ContentType),
@@ -33,6 +39,25 @@ impl Response {
         }
     }
 
+    pub fn new_csv(csv: String) -> Self {
+        let no_cache = Header::new("Cache-Control", "no-cache, no-store");
+        Self::Text(csv, ContentType::CSV, no_cache)
+    }
+
+    pub fn new_encoded<T: Serialize>(value: T, encoding: Encoding) -> Self {
+        if encoding == Encoding::Json {
+            return Self::new_json(value);
+        }
+
+        let no_cache = Header::new("Cache-Control", "no-cache, no-store");
+        match encoding.encode(&value) {
+            Ok(data) => Self::Encoded(data, encoding.content_type(), no_cache),
+            Err(e) => Self::Err {
+                inner: (http::Status::InternalServerError, e),
+            },
+        }
+    }
+
     pub fn new_image(data: Vec<u8>, format: ImageFormat) -> Self {
         let no_cache = Header::new("Cache-Control", "no-cache, no-store");
         let content_type = ContentType::parse_flexible(format.to_mime_type())
@@ -40,6 +65,11 @@ impl Response {
         Self::Image(data, content_type, no_cache)
     }
 
+    pub fn new_animated_image(data: Vec<u8>, content_type: ContentType) -> Self {
+        let no_cache = Header::new("Cache-Control", "no-cache, no-store");
+        Self::Image(data, content_type, no_cache)
+    }
+
     pub fn new_cached_image(data: Vec<u8>, format: ImageFormat) -> Self {
         let content_type = ContentType::parse_flexible(format.to_mime_type())
             .expect("Error parsing image content type");
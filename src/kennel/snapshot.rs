@@ -0,0 +1,245 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use kennel_club::{Kennel, State as SpriteState, creature, math::Vec2};
+use serde::{Deserialize, Serialize};
+
+use crate::kennel::{
+    files::write_atomic,
+    tick::{Tick, unix_millis},
+};
+
+static SNAPSHOT_VERSION: u32 = 1;
+static SNAPSHOT_PREFIX: &str = "kennel-";
+static SNAPSHOT_EXTENSION: &str = "json";
+
+#[derive(Serialize, Deserialize)]
+struct CreatureSnapshot {
+    id: String,
+    position: Vec2,
+    state: creature::State,
+    sprite_state: String,
+    sprite_state_duration: usize,
+}
+
+// what each creature was doing at some tick, enough to pick up from there
+#[derive(Serialize, Deserialize)]
+pub struct Snapshot {
+    version: u32,
+    tick: u64,
+    saved_at: u64,
+    creatures: Vec<CreatureSnapshot>,
+}
+
+impl Snapshot {
+    // `None` for a replayed tick, which has no kennel to save
+    pub fn of(tick: &Tick) -> Option<Self> {
+        tick.kennel()
+            .map(|kennel| Snapshot::new(tick.number, kennel))
+    }
+
+    pub fn new(tick: u64, kennel: &Kennel) -> Self {
+        let creatures = kennel
+            .creatures()
+            .into_iter()
+            .map(|creature| CreatureSnapshot {
+                id: creature.id.clone(),
+                position: creature.position,
+                state: creature.creature_state.clone(),
+                sprite_state: creature.sprite_state.to_string(),
+                sprite_state_duration: creature.sprite_state_duration,
+            })
+            .collect();
+
+        Snapshot {
+            version: SNAPSHOT_VERSION,
+            tick,
+            saved_at: unix_millis(SystemTime::now()),
+            creatures,
+        }
+    }
+
+    // creatures still in the kennel pick up where they were, new ones keep
+    // their fresh layout and ones that are gone are dropped, returns how many
+    // were restored
+    pub fn restore(&self, kennel: &mut Kennel) -> usize {
+        let saved = self
+            .creatures
+            .iter()
+            .map(|creature| (creature.id.as_str(), creature))
+            .collect::<HashMap<_, _>>();
+
+        let mut restored = 0;
+        for creature in kennel.creatures_mut() {
+            let Some(snapshot) = saved.get(creature.id.as_str()) else {
+                continue;
+            };
+            let Ok(sprite_state) = SpriteState::try_from(snapshot.sprite_state.as_str()) else {
+                log::warn!("Not restoring {}, unknown sprite state", creature.id);
+                continue;
+            };
+
+            creature.position = snapshot.position;
+            creature.creature_state = snapshot.state.clone();
+            creature.sprite_state = sprite_state;
+            creature.sprite_state_duration = snapshot.sprite_state_duration;
+            restored += 1;
+        }
+
+        restored
+    }
+
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    pub fn creature_count(&self) -> usize {
+        self.creatures.len()
+    }
+}
+
+fn is_snapshot(path: &Path) -> bool {
+    let name = path.file_name().and_then(|name| name.to_str());
+    name.is_some_and(|name| name.starts_with(SNAPSHOT_PREFIX))
+        && path.extension().is_some_and(|e| e == SNAPSHOT_EXTENSION)
+}
+
+// newest first, file names sort by the time they were saved
+fn list(dir: &Path) -> Vec<PathBuf> {
+    let mut paths = fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .flatten()
+                .map(|entry| entry.path())
+                .filter(|path| is_snapshot(path))
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    paths.sort();
+    paths.reverse();
+    paths
+}
+
+fn read(path: &Path) -> Result<Snapshot, String> {
+    let data = fs::read(path).map_err(|e| e.to_string())?;
+    let snapshot = serde_json::from_slice::<Snapshot>(&data).map_err(|e| e.to_string())?;
+    if snapshot.version != SNAPSHOT_VERSION {
+        return Err(format!("unsupported version {}", snapshot.version));
+    }
+    Ok(snapshot)
+}
+
+// the newest snapshot in `dir` that can still be read
+pub fn latest(dir: &Path) -> Option<Snapshot> {
+    list(dir).into_iter().find_map(|path| match read(&path) {
+        Ok(snapshot) => Some(snapshot),
+        Err(message) => {
+            log::warn!("Skipping snapshot {}: {}", path.display(), message);
+            None
+        }
+    })
+}
+
+// keeps only the newest `keep` after writing `snapshot`
+pub fn save(dir: &Path, snapshot: &Snapshot, keep: usize) -> Result<PathBuf, String> {
+    let data = serde_json::to_vec(snapshot).map_err(|e| e.to_string())?;
+
+    let name = format!(
+        "{}{:020}.{}",
+        SNAPSHOT_PREFIX, snapshot.saved_at, SNAPSHOT_EXTENSION
+    );
+    let path = dir.join(name);
+    write_atomic(&path, &data)?;
+
+    for stale in list(dir).into_iter().skip(keep.max(1)) {
+        if let Err(e) = fs::remove_file(&stale) {
+            log::warn!("Error removing snapshot {}: {}", stale.display(), e);
+        }
+    }
+
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use super::*;
+
+    fn snapshot(tick: u64, saved_at: u64) -> Snapshot {
+        Snapshot {
+            version: SNAPSHOT_VERSION,
+            tick,
+            saved_at,
+            creatures: Vec::new(),
+        }
+    }
+
+    fn temp_dir() -> PathBuf {
+        std::env::temp_dir().join(format!("kennel-snapshot-{}", Uuid::new_v4()))
+    }
+
+    fn file_name(saved_at: u64) -> String {
+        format!("{}{:020}.{}", SNAPSHOT_PREFIX, saved_at, SNAPSHOT_EXTENSION)
+    }
+
+    #[test]
+    fn restores_the_last_snapshot_saved() {
+        let dir = temp_dir();
+        save(&dir, &snapshot(7, 1), 3).unwrap();
+        save(&dir, &snapshot(8, 2), 3).unwrap();
+
+        let restored = latest(&dir);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(restored.map(|snapshot| snapshot.tick()), Some(8));
+    }
+
+    #[test]
+    fn skips_snapshots_that_cant_be_read() {
+        let dir = temp_dir();
+        save(&dir, &snapshot(7, 1), 5).unwrap();
+        fs::write(dir.join(file_name(2)), b"{\"version\": 1, \"tick\"").unwrap();
+        let newer = Snapshot {
+            version: SNAPSHOT_VERSION + 1,
+            ..snapshot(9, 3)
+        };
+        fs::write(dir.join(file_name(3)), serde_json::to_vec(&newer).unwrap()).unwrap();
+
+        let restored = latest(&dir);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(restored.map(|snapshot| snapshot.tick()), Some(7));
+    }
+
+    #[test]
+    fn keeps_only_the_newest_snapshots() {
+        let dir = temp_dir();
+        for saved_at in 1..=4 {
+            save(&dir, &snapshot(saved_at, saved_at), 2).unwrap();
+        }
+        fs::write(dir.join("notes.txt"), "not a snapshot").unwrap();
+
+        let mut names = fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect::<Vec<_>>();
+        names.sort();
+        fs::remove_dir_all(&dir).unwrap();
+
+        // nothing half-written is left behind either
+        assert_eq!(
+            names,
+            vec![file_name(3), file_name(4), "notes.txt".to_string()]
+        );
+    }
+
+    #[test]
+    fn nothing_to_restore_without_a_directory() {
+        assert!(latest(&temp_dir()).is_none());
+    }
+}
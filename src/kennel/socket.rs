@@ -0,0 +1,106 @@
+use std::{
+    fmt::Display,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Duration,
+};
+
+use rocket::{
+    futures::{Sink, SinkExt},
+    tokio::{sync::mpsc::Receiver, time::timeout},
+};
+use ws::{
+    Message,
+    frame::{CloseCode, CloseFrame},
+};
+
+use crate::{
+    kennel::{config::SlowConsumerPolicy, json::TickJson},
+    metrics::Metrics,
+};
+
+// whether `message` made it out within `send_timeout`
+pub async fn send<S>(
+    sink: &mut S,
+    message: Message,
+    send_timeout: Duration,
+    metrics: &Metrics,
+) -> bool
+where
+    S: Sink<Message> + Unpin,
+    S::Error: Display,
+{
+    match timeout(send_timeout, sink.send(message)).await {
+        Ok(Ok(())) => true,
+        Ok(Err(e)) => {
+            log::info!("Error sending kennel message: {}", e);
+            metrics.increment("kennel_ws_send_errors_total");
+            false
+        }
+        Err(_) => {
+            log::info!("Timed out sending kennel message after {:?}", send_timeout);
+            metrics.increment("kennel_ws_send_timeouts_total");
+            false
+        }
+    }
+}
+
+pub async fn close<S>(
+    sink: &mut S,
+    code: CloseCode,
+    reason: &'static str,
+    send_timeout: Duration,
+    metrics: &Metrics,
+) where
+    S: Sink<Message> + Unpin,
+    S::Error: Display,
+{
+    let frame = CloseFrame {
+        code,
+        reason: reason.into(),
+    };
+    send(sink, Message::Close(Some(frame)), send_timeout, metrics).await;
+}
+
+// how a socket keeps up when its queue of ticks fills
+pub struct Backpressure {
+    pub policy: SlowConsumerPolicy,
+    pub overflowed: Arc<AtomicBool>,
+    pub send_timeout: Duration,
+}
+
+impl Backpressure {
+    // the tick to send for `json`, or `None` once a slow client was closed;
+    // `skipped` sees every queued tick dropped on the way to the newest
+    pub async fn next<S>(
+        &self,
+        sink: &mut S,
+        receiver: &mut Receiver<TickJson>,
+        mut json: TickJson,
+        metrics: &Metrics,
+        mut skipped: impl FnMut(&TickJson),
+    ) -> Option<TickJson>
+    where
+        S: Sink<Message> + Unpin,
+        S::Error: Display,
+    {
+        match self.policy {
+            SlowConsumerPolicy::Disconnect if self.overflowed.load(Ordering::Relaxed) => {
+                metrics.increment("kennel_ws_slow_disconnects_total");
+                let reason = "Client is too slow";
+                close(sink, CloseCode::Again, reason, self.send_timeout, metrics).await;
+                return None;
+            }
+            SlowConsumerPolicy::Disconnect => {}
+            SlowConsumerPolicy::Drop => {
+                while let Ok(newer) = receiver.try_recv() {
+                    skipped(&json);
+                    json = newer;
+                }
+            }
+        }
+        Some(json)
+    }
+}
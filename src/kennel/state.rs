@@ -1,158 +1,758 @@
-use std::{collections::HashMap, path::Path, sync::Arc, time::Duration};
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::{Duration, Instant, SystemTime},
+};
 
-use kennel_club::{ImageFormat, Kennel, Sprite, State as SpriteState};
+use kennel_club::{Kennel, Sprite, State as SpriteState};
 use rand::{SeedableRng, rngs::StdRng, seq::IteratorRandom};
 use rocket::{
     futures::lock::Mutex,
     tokio::{
         self,
-        sync::mpsc::{self, Receiver, Sender},
-        time::sleep,
+        sync::{
+            Notify,
+            mpsc::{self, Receiver, Sender, error::TrySendError},
+            watch,
+        },
+        task,
+        time::{MissedTickBehavior, interval, sleep, timeout},
     },
 };
+use serde::Serialize;
 use uuid::Uuid;
 
-use crate::kennel::json::{CreatureJson, KennelJson};
-
-static IMAGE_WIDTH: u32 = 2048;
-static IMAGE_HEIGHT: u32 = 2048;
-
-type ImageResult = Option<Result<Vec<u8>, String>>;
+use crate::{
+    kennel::{
+        animate::{AnimationParams, Animator},
+        config::Config,
+        encoding::Encoding,
+        feed::{Feed, FeedMode},
+        heatmap::{self, Heatmap, HeatmapJson},
+        history::{History, HistoryJson},
+        json::{CreatureJson, KennelJson, ReloadJson, TickJson},
+        live::{Live, LiveFrame},
+        params::ImageParams,
+        recording::{self, Replay},
+        render::{self, PastImages, RenderHealth, Renderer},
+        snapshot::{self, Snapshot},
+        stats::{CreatureStats, LeaderboardJson, Stats},
+        supervisor::{Supervisor, SupervisorHealth},
+        tick::{NOTHING_TO_DRAW, Source, Tick},
+        timelapse::{self, Timelapse, TimelapseJson},
+    },
+    metrics::Metrics,
+};
 
 fn safe_rng() -> StdRng {
     let mut rng = rand::rng();
     StdRng::from_rng(&mut rng)
 }
 
+#[derive(Serialize)]
+pub struct StateHealth {
+    render: RenderHealth,
+    tick_loop: SupervisorHealth,
+}
+
+impl StateHealth {
+    pub fn is_ok(&self) -> bool {
+        self.render.is_ok() && self.tick_loop.is_ok()
+    }
+}
+
+async fn save_heatmap(config: &Config, heatmap: &Mutex<Heatmap>) -> Result<(), String> {
+    let path = heatmap::heatmap_path(config);
+    let data = heatmap.lock().await.to_bytes()?;
+    task::spawn_blocking(move || Heatmap::save(&path, &data))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+async fn save_snapshot(config: &Config, snapshot: Snapshot) -> Result<(), String> {
+    let dir = config.snapshot_dir.clone();
+    let keep = config.snapshot_keep;
+    task::spawn_blocking(move || snapshot::save(&dir, &snapshot, keep))
+        .await
+        .map_err(|e| e.to_string())?
+        .map(|_| ())
+}
+
+// runtime knobs for the tick loop, changed through the admin API
+struct Control {
+    paused: bool,
+    // ticks still to run while paused
+    steps: u64,
+    tick_interval: Duration,
+    // set through the admin API, which a replay then keeps to as well
+    interval_set: bool,
+    reseed: Option<u64>,
+    // a freshly loaded kennel to swap in on the next tick
+    replacement: Option<(Kennel, ReloadJson)>,
+}
+
+// a reloaded kennel has to hold up as well as the one it replaces
+fn validate(kennel: &Kennel) -> Result<(), String> {
+    let mut ids = HashSet::new();
+    for creature in kennel.creatures() {
+        if !ids.insert(creature.id.clone()) {
+            return Err(format!("Duplicate creature id {}", creature.id));
+        }
+    }
+
+    render::render(kennel).map(|_| ())
+}
+
+// creatures that survive a reload keep where they were and what they were doing
+fn carry_over(previous: &Tick, mut kennel: Kennel) -> Kennel {
+    if let Some(snapshot) = Snapshot::of(previous) {
+        snapshot.restore(&mut kennel);
+    }
+    kennel
+}
+
+// a replay keeps to the pace of the recording, unless one was set since
+async fn effective_interval(control: &Control, replay: Option<&Mutex<Replay>>) -> Duration {
+    match replay {
+        Some(replay) if !control.interval_set => replay.lock().await.pace(),
+        _ => control.tick_interval,
+    }
+}
+
+#[derive(Serialize)]
+pub struct KennelStatus {
+    paused: bool,
+    steps: u64,
+    tick: u64,
+    tick_interval_ms: u64,
+}
+
+struct Subscriber {
+    sender: Sender<TickJson>,
+    overflowed: Arc<AtomicBool>,
+}
+
+pub struct Subscription {
+    pub id: Uuid,
+    pub receiver: Receiver<TickJson>,
+    // set once a tick had to be dropped because the queue was full
+    pub overflowed: Arc<AtomicBool>,
+}
+
 pub struct State {
-    kennel: Arc<Mutex<Kennel>>,
+    instance: String,
+    dir: PathBuf,
+    current: Arc<Mutex<Tick>>,
+    replay: Option<Arc<Mutex<Replay>>>,
+    // where sprites come from while replaying, if the data directory loads
+    sprites: Option<Arc<Kennel>>,
+    history: Arc<Mutex<History>>,
+    heatmap: Arc<Mutex<Heatmap>>,
+    stats: Arc<Mutex<Stats>>,
+    control: Arc<Mutex<Control>>,
+    // wakes the tick loop early so control changes apply right away
+    wake: Arc<Notify>,
+    config: Config,
     is_shutdown: Arc<Mutex<bool>>,
-    image_cache: Arc<Mutex<ImageResult>>,
-    subscribers: Arc<Mutex<HashMap<Uuid, Sender<KennelJson>>>>,
+    renderer: Arc<Renderer>,
+    animator: Animator,
+    past_images: PastImages,
+    live: Arc<Live>,
+    timelapse: Arc<Timelapse>,
+    subscribers: Arc<Mutex<HashMap<Uuid, Subscriber>>>,
+    // one reload at a time
+    reloading: Mutex<()>,
+    supervisor: Arc<Supervisor>,
+    metrics: Arc<Metrics>,
+}
+
+// everything the tick loop shares with `State`, cloned for every (re)start
+#[derive(Clone)]
+struct TickLoop {
+    current: Arc<Mutex<Tick>>,
+    history: Arc<Mutex<History>>,
+    heatmap: Arc<Mutex<Heatmap>>,
+    stats: Arc<Mutex<Stats>>,
+    control: Arc<Mutex<Control>>,
+    wake: Arc<Notify>,
+    is_shutdown: Arc<Mutex<bool>>,
+    renderer: Arc<Renderer>,
+    live: Arc<Live>,
+    subscribers: Arc<Mutex<HashMap<Uuid, Subscriber>>>,
+    supervisor: Arc<Supervisor>,
+    recorder: Option<Sender<TickJson>>,
+    // played back in place of the simulation
+    replay: Option<Arc<Mutex<Replay>>>,
+    metrics: Arc<Metrics>,
+}
+
+impl TickLoop {
+    // the recorder writes on its own, falling behind loses ticks from the
+    // recording rather than holding up the kennel
+    fn record(&self, json: &TickJson) {
+        let Some(recorder) = &self.recorder else {
+            return;
+        };
+        if recorder.try_send(json.clone()).is_err() {
+            self.metrics.increment("kennel_record_dropped_total");
+        }
+    }
+
+    async fn run(self) {
+        let mut kennel_rng = safe_rng();
+        let mut last_tick_at = Instant::now();
+
+        loop {
+            // graceful shutdown
+            let is_shutdown = self.is_shutdown.lock().await;
+            if *is_shutdown {
+                break;
+            }
+            drop(is_shutdown);
+
+            // wait out what is left of the interval since the last tick, or
+            // for a step while paused, but swap in a reloaded kennel right away
+            let control = self.control.lock().await;
+            let tick_interval = effective_interval(&control, self.replay.as_deref()).await;
+            let wait = match (control.paused, control.steps) {
+                _ if control.replacement.is_some() => Some(Duration::ZERO),
+                (false, _) => Some(tick_interval.saturating_sub(last_tick_at.elapsed())),
+                (true, 0) => None,
+                (true, _) => Some(Duration::ZERO),
+            };
+            drop(control);
+
+            let woken = match wait {
+                Some(wait) => timeout(wait, self.wake.notified()).await.is_ok(),
+                None => {
+                    self.wake.notified().await;
+                    true
+                }
+            };
+            if woken {
+                continue;
+            }
+
+            // control may have changed while waiting
+            let mut control = self.control.lock().await;
+            let replacement = control.replacement.take();
+            if control.paused && replacement.is_none() {
+                if control.steps == 0 {
+                    continue;
+                }
+                control.steps -= 1;
+            }
+            if let Some(seed) = control.reseed.take() {
+                kennel_rng = StdRng::seed_from_u64(seed);
+            }
+            let tick_interval = effective_interval(&control, self.replay.as_deref()).await;
+            drop(control);
+            last_tick_at = Instant::now();
+
+            // update kennel state
+            let mut current = self.current.lock().await;
+            let (source, reload) = match (replacement, &self.replay) {
+                (Some((kennel, reload)), _) => {
+                    (Source::from(carry_over(&current, kennel)), Some(reload))
+                }
+                // a replayed tick is the recorded one, reload notices and all
+                (None, Some(replay)) => {
+                    let next = replay.lock().await.next().await;
+                    let Some(json) = next else {
+                        drop(current);
+                        log::info!("Replay finished, pausing the kennel");
+                        self.control.lock().await.paused = true;
+                        continue;
+                    };
+                    let reload = json.reload().cloned();
+                    (Source::Recorded(Arc::new(json)), reload)
+                }
+                (None, None) => {
+                    let kennel = current.kennel().expect("Simulated ticks have a kennel");
+                    match kennel.next(&mut kennel_rng) {
+                        Ok(kennel) => (Source::from(kennel), None),
+                        // stay on the last good state and try again after a while
+                        Err(message) => {
+                            let context = format!("Error generating tick {}", current.number + 1);
+                            drop(current);
+                            let backoff = self.supervisor.record_error(&context, message).await;
+                            let _ = timeout(backoff, self.wake.notified()).await;
+                            continue;
+                        }
+                    }
+                }
+            };
+            let next_tick = current.next(source);
+
+            let mut subscribers = self.subscribers.lock().await;
+
+            // never wait on a subscriber, a full queue loses this tick instead
+            let tick_json = TickJson::new(&next_tick, tick_interval).with_reload(reload);
+            subscribers.retain(|_, subscriber| {
+                match subscriber.sender.try_send(tick_json.clone()) {
+                    Ok(()) => true,
+                    Err(TrySendError::Full(_)) => {
+                        subscriber.overflowed.store(true, Ordering::Relaxed);
+                        self.metrics
+                            .increment("kennel_subscriber_ticks_dropped_total");
+                        true
+                    }
+                    Err(TrySendError::Closed(_)) => false,
+                }
+            });
+            self.metrics
+                .set_gauge("kennel_subscribers", subscribers.len() as i64);
+            drop(subscribers);
+
+            self.heatmap.lock().await.add(&tick_json);
+            self.stats.lock().await.add(&tick_json);
+            self.record(&tick_json);
+
+            // keep recent ticks around for resuming clients and the history API
+            let mut history = self.history.lock().await;
+            history.push(tick_json, &next_tick);
+            drop(history);
+
+            *current = next_tick.clone();
+            drop(current);
+            self.supervisor.record_success().await;
+
+            // refresh or clear image cache
+            if let Some(kennel) = next_tick.kennel() {
+                self.live.on_tick(kennel.clone(), next_tick.number);
+                self.renderer
+                    .on_tick(kennel.clone(), next_tick.number)
+                    .await;
+            }
+        }
+    }
 }
 
 impl State {
-    pub fn load(dir: &Path) -> Result<Self, String> {
+    pub fn load(dir: &Path, config: &Config, metrics: Arc<Metrics>) -> Result<Self, String> {
+        let replay = match &config.replay {
+            Some(path) => Some(Replay::load(path, config.replay_loop, config.replay_speed)?),
+            None => None,
+        };
+
+        // a replay starts where the recording does and only needs the data
+        // directory for sprites, otherwise we pick up from the last snapshot
         let mut init_rng = safe_rng();
-        let kennel = Kennel::load(dir, &mut init_rng)?;
-        let subscribers: HashMap<Uuid, Sender<KennelJson>> = HashMap::new();
+        let (tick, sprites) = match &replay {
+            Some(replay) => {
+                let sprites = match Kennel::load(dir, &mut init_rng) {
+                    Ok(kennel) => Some(Arc::new(kennel)),
+                    Err(message) => {
+                        log::warn!(
+                            "Replaying without sprites, the kennel didn't load: {}",
+                            message
+                        );
+                        None
+                    }
+                };
+                let first = Tick::first(Source::Recorded(Arc::new(replay.first().clone())));
+                (first, sprites)
+            }
+            None => {
+                let mut kennel = Kennel::load(dir, &mut init_rng)?;
+                if let Some(snapshot) = snapshot::latest(&config.snapshot_dir) {
+                    let restored = snapshot.restore(&mut kennel);
+                    log::info!(
+                        "Restored {} of {} creatures from tick {} snapshot",
+                        restored,
+                        snapshot.creature_count(),
+                        snapshot.tick()
+                    );
+                }
+                (Tick::first(kennel), None)
+            }
+        };
+        if replay.is_some() && config.record {
+            log::warn!("Not recording while replaying a recording");
+        }
+        let configured_interval = Duration::from_millis(config.tick_interval_ms);
+        let tick_interval = replay.as_ref().map_or(configured_interval, Replay::pace);
+        let replay = replay.map(|replay| Arc::new(Mutex::new(replay)));
+        let subscribers: HashMap<Uuid, Subscriber> = HashMap::new();
+        let renderer = Arc::new(Renderer::new(config.eager_render, metrics.clone()));
+        let live = Arc::new(Live::new(config, metrics.clone()));
+        // resuming needs at least `resume_window` ticks of history
+        let mut history = History::new(
+            config.history_ticks.max(config.resume_window),
+            config.history_kennels,
+        );
+        history.push(TickJson::new(&tick, tick_interval), &tick);
+        let control = Control {
+            paused: false,
+            steps: 0,
+            tick_interval: configured_interval,
+            interval_set: false,
+            reseed: None,
+            replacement: None,
+        };
 
-        let kennel_rc = Arc::new(Mutex::new(kennel));
+        let current_rc = Arc::new(Mutex::new(tick.clone()));
+        let history_rc = Arc::new(Mutex::new(history));
+        // a replay counts from scratch and never touches the saved heatmap
+        let heatmap = match config.replay {
+            Some(_) => Heatmap::new(config),
+            None => Heatmap::load(&heatmap::heatmap_path(config), config),
+        };
+        let heatmap_rc = Arc::new(Mutex::new(heatmap));
+        let mut stats = Stats::default();
+        stats.add(&TickJson::new(&tick, tick_interval));
+        let stats_rc = Arc::new(Mutex::new(stats));
+        let control_rc = Arc::new(Mutex::new(control));
+        let wake_rc = Arc::new(Notify::new());
         let is_shutdown_rc = Arc::new(Mutex::new(false));
-        let image_cache_rc = Arc::new(Mutex::new(None));
         let subscribers_rc = Arc::new(Mutex::new(subscribers));
 
-        let thread_kennel = kennel_rc.clone();
-        let thread_is_shutdown = is_shutdown_rc.clone();
-        let thread_image_cache = image_cache_rc.clone();
-        let thread_subscribers = subscribers_rc.clone();
+        let supervisor = Arc::new(Supervisor::new("kennel tick loop", metrics.clone()));
+        let tick_loop = TickLoop {
+            current: current_rc.clone(),
+            history: history_rc.clone(),
+            heatmap: heatmap_rc.clone(),
+            stats: stats_rc.clone(),
+            control: control_rc.clone(),
+            wake: wake_rc.clone(),
+            is_shutdown: is_shutdown_rc.clone(),
+            renderer: renderer.clone(),
+            live: live.clone(),
+            subscribers: subscribers_rc.clone(),
+            supervisor: supervisor.clone(),
+            recorder: (config.record && replay.is_none())
+                .then(|| recording::start(config, metrics.clone())),
+            replay: replay.clone(),
+            metrics: metrics.clone(),
+        };
+        tick_loop.record(&TickJson::new(&tick, tick_interval));
 
-        tokio::spawn(async move {
-            let mut kennel_rng = safe_rng();
-            loop {
-                // graceful shutdown
-                let is_shutdown = thread_is_shutdown.lock().await;
-                if *is_shutdown {
-                    break;
+        // a replay would only overwrite the live snapshots with old state
+        if config.replay.is_none() {
+            let thread_current = current_rc.clone();
+            let thread_heatmap = heatmap_rc.clone();
+            let thread_is_shutdown = is_shutdown_rc.clone();
+            let snapshot_config = config.clone();
+            tokio::spawn(async move {
+                let interval =
+                    Duration::from_millis(snapshot_config.snapshot_interval_ms.max(1000));
+                loop {
+                    sleep(interval).await;
+                    if *thread_is_shutdown.lock().await {
+                        break;
+                    }
+                    let snapshot = Snapshot::of(&*thread_current.lock().await);
+                    let saved = match snapshot {
+                        Some(snapshot) => save_snapshot(&snapshot_config, snapshot).await,
+                        None => Ok(()),
+                    };
+                    if let Err(message) = saved {
+                        log::warn!("Error saving kennel snapshot: {}", message);
+                    }
+                    if let Err(message) = save_heatmap(&snapshot_config, &thread_heatmap).await {
+                        log::warn!("Error saving kennel heatmap: {}", message);
+                    }
                 }
-                drop(is_shutdown);
-
-                sleep(Duration::from_secs(1)).await;
+            });
+        }
 
-                // update kennel state
-                let mut kennel = thread_kennel.lock().await;
-                let next_kennel = kennel
-                    .next(&mut kennel_rng)
-                    .expect("Error generating next kennel state");
+        // a replay has nothing to draw
+        let timelapse = Arc::new(Timelapse::new(config));
+        if config.timelapse && config.replay.is_none() {
+            let thread_current = current_rc.clone();
+            let thread_is_shutdown = is_shutdown_rc.clone();
+            let thread_timelapse = timelapse.clone();
+            let sample_interval = Duration::from_millis(config.timelapse_sample_ms.max(1000));
+            tokio::spawn(async move {
+                let mut samples = interval(sample_interval);
+                samples.set_missed_tick_behavior(MissedTickBehavior::Skip);
+                loop {
+                    samples.tick().await;
+                    if *thread_is_shutdown.lock().await {
+                        break;
+                    }
 
-                let subscribers = thread_subscribers.lock().await;
-
-                let kennel_json = KennelJson::from(&next_kennel);
-                for subscriber in subscribers.values() {
-                    let _ = subscriber.send(kennel_json.clone()).await;
+                    let Some(kennel) = thread_current.lock().await.kennel().cloned() else {
+                        continue;
+                    };
+                    let sampler = thread_timelapse.clone();
+                    let sampled = task::spawn_blocking(move || {
+                        // a new day finishes off the one before it
+                        let now = SystemTime::now();
+                        sampler.compile_pending(&timelapse::utc_date(now));
+                        sampler.sample(&kennel, now)
+                    })
+                    .await
+                    .map_err(|e| e.to_string())
+                    .and_then(|result| result);
+                    if let Err(message) = sampled {
+                        log::warn!("Error sampling kennel time-lapse frame: {}", message);
+                    }
                 }
-                drop(subscribers);
-
-                *kennel = next_kennel;
-                drop(kennel);
+            });
+        }
 
-                // clear image cache
-                let mut image_cache = thread_image_cache.lock().await;
-                image_cache.take();
-                drop(image_cache);
+        let thread_renderer = renderer.clone();
+        let thread_supervisor = supervisor.clone();
+        tokio::spawn(async move {
+            if let Some(kennel) = tick.kennel() {
+                thread_renderer.on_tick(kennel.clone(), tick.number).await;
             }
+
+            // a panic restarts the loop from the last tick that made it into `current`
+            thread_supervisor
+                .supervise(move || tick_loop.clone().run())
+                .await;
         });
 
         Ok(State {
-            kennel: kennel_rc,
+            instance: Uuid::new_v4().simple().to_string(),
+            dir: dir.to_path_buf(),
+            current: current_rc,
+            replay,
+            sprites,
+            history: history_rc,
+            heatmap: heatmap_rc,
+            stats: stats_rc,
+            control: control_rc,
+            wake: wake_rc,
+            config: config.clone(),
             is_shutdown: is_shutdown_rc,
-            image_cache: image_cache_rc,
+            renderer,
+            animator: Animator::default(),
+            past_images: PastImages::default(),
+            live,
+            timelapse,
             subscribers: subscribers_rc,
+            reloading: Mutex::new(()),
+            supervisor,
+            metrics,
         })
     }
 
-    pub async fn as_image(&self, format: ImageFormat) -> Result<Vec<u8>, String> {
-        let mut image_cache = self.image_cache.lock().await;
-        let kennel = self.kennel.lock().await;
-        let cache_result = image_cache
-            .get_or_insert_with(move || kennel.get_image(IMAGE_WIDTH, IMAGE_HEIGHT, format));
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    // replayed ticks only have their JSON, nothing can be drawn
+    pub fn is_replay(&self) -> bool {
+        self.replay.is_some()
+    }
+
+    pub async fn as_image(&self, params: &ImageParams) -> Result<Vec<u8>, String> {
+        // the tick loop isn't held up while a frame renders
+        let (kennel, generation) = {
+            let current = self.current.lock().await;
+            (current.kennel().cloned(), current.number)
+        };
+        let kennel = kennel.ok_or(NOTHING_TO_DRAW)?;
+        self.renderer.get(kennel, generation, params).await
+    }
 
-        cache_result
-            .as_ref()
-            .map(|data| data.to_vec())
-            .map_err(|message| message.clone())
+    pub async fn health(&self) -> StateHealth {
+        StateHealth {
+            render: self.renderer.health().await,
+            tick_loop: self.supervisor.health().await,
+        }
     }
 
     pub async fn as_json(&self) -> Vec<CreatureJson> {
-        let kennel = self.kennel.lock().await;
+        let current = self.current.lock().await;
+        KennelJson::from(&current.source).into_creatures()
+    }
 
-        kennel
-            .creatures()
-            .into_iter()
-            .map(CreatureJson::from)
-            .collect()
+    pub async fn as_tick_json(&self) -> TickJson {
+        let control = self.control.lock().await;
+        let tick_interval = effective_interval(&control, self.replay.as_deref()).await;
+        drop(control);
+        let current = self.current.lock().await;
+        TickJson::new(&current, tick_interval)
+    }
+
+    // `resume` is a token from an earlier feed message of this server instance,
+    // `<instance>.<tick>`, or a bare tick number that is only trusted as far as
+    // the history still has it
+    pub async fn feed(&self, mode: FeedMode, encoding: Encoding, resume: Option<&str>) -> Feed {
+        let resume_tick = resume.and_then(|token| match token.split_once('.') {
+            Some((instance, tick)) if instance == self.instance => tick.parse::<u64>().ok(),
+            Some(_) => None,
+            None => token.parse::<u64>().ok(),
+        });
+
+        let resumed = match resume_tick {
+            Some(tick) => {
+                let history = self.history.lock().await;
+                history.get(tick).cloned()
+            }
+            None => None,
+        };
+
+        Feed::new(
+            mode,
+            encoding,
+            self.instance.clone(),
+            self.config.keyframe_interval,
+            resumed,
+        )
+    }
+
+    pub async fn history(&self, since: Option<u64>, limit: usize) -> HistoryJson {
+        self.history.lock().await.since(since, limit)
+    }
+
+    pub async fn tick_json_at(&self, tick: u64) -> Option<TickJson> {
+        self.history.lock().await.get(tick).cloned()
+    }
+
+    // only the newest `history_kennels` ticks can still be drawn
+    pub async fn image_at(
+        &self,
+        tick: u64,
+        params: &ImageParams,
+    ) -> Option<Result<Vec<u8>, String>> {
+        let kennel = self.history.lock().await.kennel_at(tick)?.kennel()?.clone();
+        Some(self.past_images.get(kennel, tick, params).await)
+    }
+
+    // the last few seconds of kennels still in history, as one animated image
+    pub async fn animation(&self, params: &AnimationParams) -> Option<Result<Vec<u8>, String>> {
+        let ticks = self
+            .history
+            .lock()
+            .await
+            .kennels_within(params.window(), self.config.animation_max_frames.max(1));
+        if ticks.is_empty() {
+            return None;
+        }
+        Some(self.animator.get(ticks, params).await)
+    }
+
+    // rendered frames for the MJPEG stream, starting with the current tick
+    pub async fn live_frames(&self) -> watch::Receiver<Option<Arc<LiveFrame>>> {
+        let frames = self.live.subscribe();
+        let current = self.current.lock().await;
+        if let Some(kennel) = current
+            .kennel()
+            .filter(|_| self.live.is_stale(current.number))
+        {
+            self.live.on_tick(kennel.clone(), current.number);
+        }
+        frames
+    }
+
+    // `None` for a creature that hasn't been counted
+    pub async fn heatmap(&self, creature: Option<&str>) -> Option<HeatmapJson> {
+        self.heatmap.lock().await.to_json(creature)
+    }
+
+    pub async fn heatmap_image(
+        &self,
+        creature: Option<&str>,
+        size: u32,
+    ) -> Option<Result<Vec<u8>, String>> {
+        let heatmap = self.heatmap.lock().await;
+        let grid = heatmap.grid(creature)?.to_vec();
+        let cells = heatmap.cells();
+        drop(heatmap);
+
+        let Some(kennel) = self.current.lock().await.kennel().cloned() else {
+            return Some(Err(NOTHING_TO_DRAW.to_string()));
+        };
+        let rendered = task::spawn_blocking(move || heatmap::render(&kennel, &grid, cells, size))
+            .await
+            .map_err(|e| e.to_string())
+            .and_then(|result| result);
+        Some(rendered)
+    }
+
+    pub async fn creature_stats(&self, id: &str) -> Option<CreatureStats> {
+        self.stats.lock().await.creature(id)
+    }
+
+    pub async fn leaderboard(
+        &self,
+        sort: &str,
+        ascending: bool,
+        limit: usize,
+    ) -> Result<LeaderboardJson, String> {
+        self.stats.lock().await.leaderboard(sort, ascending, limit)
+    }
+
+    pub async fn timelapses(&self) -> Vec<TimelapseJson> {
+        let timelapse = self.timelapse.clone();
+        task::spawn_blocking(move || timelapse.index())
+            .await
+            .unwrap_or_default()
+    }
+
+    pub async fn timelapse(&self, date: &str) -> Option<Vec<u8>> {
+        let path = self.timelapse.path(date)?;
+        tokio::fs::read(path).await.ok()
     }
 
     pub async fn get_creature(&self, id: &str) -> Option<CreatureJson> {
-        let kennel = self.kennel.lock().await;
+        let current = self.current.lock().await;
 
-        kennel
-            .creatures()
+        KennelJson::from(&current.source)
+            .into_creatures()
             .into_iter()
-            .find(|creature| creature.id == id)
-            .map(CreatureJson::from)
+            .find(|creature| creature.id() == id)
     }
 
     pub async fn get_random_creature(&self) -> Option<CreatureJson> {
         let mut rng = safe_rng();
-        let kennel = self.kennel.lock().await;
+        let current = self.current.lock().await;
 
-        kennel
-            .creatures()
+        KennelJson::from(&current.source)
+            .into_creatures()
             .into_iter()
             .choose(&mut rng)
-            .map(CreatureJson::from)
+    }
+
+    // the running kennel, or the one loaded for its sprites while replaying
+    async fn sprites(&self) -> Option<Arc<Kennel>> {
+        let current = self.current.lock().await;
+        current.kennel().cloned().or_else(|| self.sprites.clone())
     }
 
     pub async fn get_sprite(&self, id: &str) -> Option<Sprite> {
-        let kennel = self.kennel.lock().await;
-        kennel.get_sprite(id).cloned()
+        self.sprites().await?.get_sprite(id).cloned()
     }
 
-    pub async fn get_sprite_by(&self, id: &str, sprite_state: &str, frame: &usize) -> Option<Sprite> {
-        let kennel = self.kennel.lock().await;
+    pub async fn get_sprite_by(
+        &self,
+        id: &str,
+        sprite_state: &str,
+        frame: &usize,
+    ) -> Option<Sprite> {
+        let sprites = self.sprites().await?;
         SpriteState::try_from(sprite_state)
             .ok()
-            .and_then(|s| kennel.get_sprite_by(id, &s, frame).cloned())
+            .and_then(|s| sprites.get_sprite_by(id, &s, frame).cloned())
     }
 
-    pub async fn subscribe(&self) -> (Uuid, Receiver<KennelJson>) {
+    pub async fn subscribe(&self) -> Subscription {
         let mut subscribers = self.subscribers.lock().await;
-        let (tx, rx) = mpsc::channel(1);
+        let (tx, rx) = mpsc::channel(self.config.ws_queue_size.max(1));
         let id = Uuid::new_v4();
+        let overflowed = Arc::new(AtomicBool::new(false));
 
-        subscribers.insert(id, tx);
-        (id, rx)
+        subscribers.insert(
+            id,
+            Subscriber {
+                sender: tx,
+                overflowed: overflowed.clone(),
+            },
+        );
+        Subscription {
+            id,
+            receiver: rx,
+            overflowed,
+        }
     }
 
     pub async fn unsubscribe(&self, id: &Uuid) {
@@ -163,5 +763,121 @@ impl State {
     pub async fn shutdown(&self) {
         let mut is_shutdown = self.is_shutdown.lock().await;
         *is_shutdown = true;
+        self.wake.notify_one();
+        drop(is_shutdown);
+        if self.config.replay.is_some() {
+            return;
+        }
+
+        // one last snapshot so the next start picks up right here
+        let snapshot = Snapshot::of(&*self.current.lock().await);
+        if let Some(snapshot) = snapshot {
+            match save_snapshot(&self.config, snapshot).await {
+                Ok(()) => log::info!("Saved kennel snapshot on shutdown"),
+                Err(message) => log::warn!("Error saving kennel snapshot: {}", message),
+            }
+        }
+        if let Err(message) = save_heatmap(&self.config, &self.heatmap).await {
+            log::warn!("Error saving kennel heatmap: {}", message);
+        }
+    }
+
+    pub async fn status(&self) -> KennelStatus {
+        let control = self.control.lock().await;
+        let tick_interval = effective_interval(&control, self.replay.as_deref()).await;
+        let current = self.current.lock().await;
+        KennelStatus {
+            paused: control.paused,
+            steps: control.steps,
+            tick: current.number,
+            tick_interval_ms: tick_interval.as_millis() as u64,
+        }
+    }
+
+    pub async fn pause(&self) {
+        let mut control = self.control.lock().await;
+        control.paused = true;
+        self.wake.notify_one();
+    }
+
+    pub async fn resume(&self) {
+        let mut control = self.control.lock().await;
+        control.paused = false;
+        control.steps = 0;
+        self.wake.notify_one();
+    }
+
+    // run `count` more ticks, one right after the other
+    pub async fn step(&self, count: u64) -> Result<(), String> {
+        let mut control = self.control.lock().await;
+        if !control.paused {
+            return Err("Kennel must be paused to step".to_string());
+        }
+
+        control.steps = control.steps.saturating_add(count);
+        self.wake.notify_one();
+        Ok(())
+    }
+
+    pub async fn set_tick_interval(&self, tick_interval: Duration) {
+        let mut control = self.control.lock().await;
+        control.tick_interval = tick_interval;
+        control.interval_set = true;
+        self.wake.notify_one();
+    }
+
+    // takes effect from the next tick
+    pub async fn reseed(&self, seed: u64) {
+        let mut control = self.control.lock().await;
+        control.reseed = Some(seed);
+        self.wake.notify_one();
+    }
+
+    // loads the data directory again and swaps it in on the next tick, leaving
+    // the running kennel alone if anything about the new one is off
+    pub async fn reload(&self) -> Result<ReloadJson, String> {
+        if self.is_replay() {
+            return Err("The kennel can't be reloaded while replaying a recording".to_string());
+        }
+
+        let _reloading = self.reloading.lock().await;
+        let dir = self.dir.clone();
+        let loaded = task::spawn_blocking(move || {
+            let kennel = Kennel::load(&dir, &mut safe_rng())?;
+            validate(&kennel)?;
+            Ok::<_, String>(kennel)
+        })
+        .await
+        .map_err(|e| e.to_string())
+        .and_then(|result| result);
+
+        let kennel = match loaded {
+            Ok(kennel) => kennel,
+            Err(message) => {
+                log::warn!("Error reloading kennel: {}", message);
+                self.metrics.increment("kennel_reload_failures_total");
+                return Err(message);
+            }
+        };
+
+        let previous = KennelJson::from(&self.current.lock().await.source);
+        let reload = ReloadJson::between(&previous, &KennelJson::from(&kennel));
+        let mut control = self.control.lock().await;
+        control.replacement = Some((kennel, reload.clone()));
+        self.wake.notify_one();
+        self.metrics.increment("kennel_reloads_total");
+        Ok(reload)
+    }
+
+    // every creature as if just added, for a kennel that only now came online
+    pub async fn roster(&self) -> ReloadJson {
+        let current = self.current.lock().await;
+        ReloadJson::loaded(&KennelJson::from(&current.source))
+    }
+
+    pub async fn clear_render_cache(&self) {
+        self.renderer.clear().await;
+        self.animator.clear().await;
+        self.past_images.clear().await;
     }
 }
@@ -0,0 +1,291 @@
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, BTreeSet, HashMap},
+};
+
+use serde::Serialize;
+
+use crate::kennel::json::{PositionJson, TickJson};
+
+// longer gaps between ticks, from a pause or a restart, only count this much
+static MAX_GAP_MS: u64 = 60000;
+// movement below this is just jitter, not activity
+static MIN_MOVEMENT: f64 = 1e-3;
+// what the leaderboard ranks by besides time in a state
+static SORT_KEYS: [&str; 8] = [
+    "distance",
+    "speed",
+    "average_speed",
+    "transitions",
+    "idle",
+    "idle_ms",
+    "tracked",
+    "tracked_ms",
+];
+
+#[derive(Serialize, Clone)]
+pub struct CreatureStats {
+    id: String,
+    // how long this creature has been followed, since it joined the kennel or
+    // the server started
+    tracked_ms: u64,
+    state_ms: BTreeMap<String, u64>,
+    distance: f64,
+    // distance per second over `tracked_ms`
+    average_speed: f64,
+    transitions: u64,
+    last_active_at: u64,
+    idle_ms: u64,
+}
+
+impl CreatureStats {
+    // what the leaderboard ranks by, a fixed field or time in a given state
+    fn key(&self, sort: &str) -> f64 {
+        match sort {
+            "distance" => self.distance,
+            "speed" | "average_speed" => self.average_speed,
+            "transitions" => self.transitions as f64,
+            "idle" | "idle_ms" => self.idle_ms as f64,
+            "tracked" | "tracked_ms" => self.tracked_ms as f64,
+            state => self.state_ms.get(state).copied().unwrap_or_default() as f64,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct RankedStats {
+    rank: usize,
+    #[serde(flatten)]
+    stats: CreatureStats,
+}
+
+#[derive(Serialize)]
+pub struct LeaderboardJson {
+    sort: String,
+    creatures: Vec<RankedStats>,
+}
+
+struct Tracked {
+    tracked_ms: u64,
+    state_ms: HashMap<String, u64>,
+    distance: f64,
+    transitions: u64,
+    last_active_at: u64,
+    state: String,
+    position: PositionJson,
+}
+
+// stats for every creature in the kennel, built up from consecutive ticks
+#[derive(Default)]
+pub struct Stats {
+    creatures: HashMap<String, Tracked>,
+    updated_at: u64,
+}
+
+impl Stats {
+    pub fn add(&mut self, json: &TickJson) {
+        let now = json.timestamp();
+        let elapsed = now.saturating_sub(self.updated_at).min(MAX_GAP_MS);
+        self.updated_at = now;
+
+        // creatures that left the kennel start over if they come back
+        let present = json
+            .creatures()
+            .iter()
+            .map(|creature| creature.id())
+            .collect::<BTreeSet<_>>();
+        self.creatures.retain(|id, _| present.contains(id.as_str()));
+
+        for creature in json.creatures().iter() {
+            let state = creature.state_name();
+            let position = creature.position();
+            if !self.creatures.contains_key(creature.id()) {
+                self.creatures.insert(
+                    creature.id().to_string(),
+                    Tracked {
+                        tracked_ms: 0,
+                        state_ms: HashMap::new(),
+                        distance: 0.0,
+                        transitions: 0,
+                        last_active_at: now,
+                        state,
+                        position,
+                    },
+                );
+                continue;
+            }
+
+            // the time since the last tick was spent in the state it had then
+            let tracked = self
+                .creatures
+                .get_mut(creature.id())
+                .expect("Creature is tracked");
+            tracked.tracked_ms += elapsed;
+            *tracked.state_ms.entry(tracked.state.clone()).or_default() += elapsed;
+
+            let moved = (position.x - tracked.position.x).hypot(position.y - tracked.position.y);
+            let changed = state != tracked.state;
+            tracked.distance += moved;
+            if changed {
+                tracked.transitions += 1;
+            }
+            if changed || moved > MIN_MOVEMENT {
+                tracked.last_active_at = now;
+            }
+            tracked.state = state;
+            tracked.position = position;
+        }
+    }
+
+    fn to_stats(&self, id: &str, tracked: &Tracked) -> CreatureStats {
+        let seconds = tracked.tracked_ms as f64 / 1000.0;
+        CreatureStats {
+            id: id.to_string(),
+            tracked_ms: tracked.tracked_ms,
+            state_ms: tracked
+                .state_ms
+                .iter()
+                .map(|(state, ms)| (state.clone(), *ms))
+                .collect(),
+            distance: tracked.distance,
+            average_speed: if seconds > 0.0 {
+                tracked.distance / seconds
+            } else {
+                0.0
+            },
+            transitions: tracked.transitions,
+            last_active_at: tracked.last_active_at,
+            idle_ms: self.updated_at.saturating_sub(tracked.last_active_at),
+        }
+    }
+
+    pub fn creature(&self, id: &str) -> Option<CreatureStats> {
+        let tracked = self.creatures.get(id)?;
+        Some(self.to_stats(id, tracked))
+    }
+
+    // a fixed field, or a state some creature has been in
+    fn is_sort_key(&self, sort: &str) -> bool {
+        SORT_KEYS.contains(&sort)
+            || self
+                .creatures
+                .values()
+                .any(|tracked| tracked.state == sort || tracked.state_ms.contains_key(sort))
+    }
+
+    // highest first unless `ascending`, ties broken by id
+    pub fn leaderboard(
+        &self,
+        sort: &str,
+        ascending: bool,
+        limit: usize,
+    ) -> Result<LeaderboardJson, String> {
+        if !self.is_sort_key(sort) {
+            return Err(format!("Unsupported sort `{}`", sort));
+        }
+
+        let mut stats = self
+            .creatures
+            .iter()
+            .map(|(id, tracked)| self.to_stats(id, tracked))
+            .collect::<Vec<_>>();
+        stats.sort_by(|a, b| {
+            let order = a.key(sort).total_cmp(&b.key(sort));
+            let order = if ascending { order } else { order.reverse() };
+            match order {
+                Ordering::Equal => a.id.cmp(&b.id),
+                order => order,
+            }
+        });
+
+        Ok(LeaderboardJson {
+            sort: sort.to_string(),
+            creatures: stats
+                .into_iter()
+                .take(limit)
+                .enumerate()
+                .map(|(i, stats)| RankedStats { rank: i + 1, stats })
+                .collect(),
+        })
+    }
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+// one row per creature, with a `<state>_ms` column for every state any of
+// them has been in
+pub fn to_csv<'a>(rows: impl IntoIterator<Item = (Option<usize>, &'a CreatureStats)>) -> String {
+    let rows = rows.into_iter().collect::<Vec<_>>();
+    let states = rows
+        .iter()
+        .flat_map(|(_, stats)| stats.state_ms.keys())
+        .collect::<BTreeSet<_>>();
+    let ranked = rows.iter().any(|(rank, _)| rank.is_some());
+
+    let mut header = Vec::new();
+    if ranked {
+        header.push("rank".to_string());
+    }
+    header.extend(
+        [
+            "id",
+            "tracked_ms",
+            "distance",
+            "average_speed",
+            "transitions",
+            "last_active_at",
+            "idle_ms",
+        ]
+        .map(str::to_string),
+    );
+    header.extend(
+        states
+            .iter()
+            .map(|state| csv_field(&format!("{}_ms", state))),
+    );
+
+    let mut csv = header.join(",");
+    csv.push_str("\r\n");
+    for &(rank, stats) in rows.iter() {
+        let mut row = Vec::new();
+        if ranked {
+            row.push(rank.map(|rank| rank.to_string()).unwrap_or_default());
+        }
+        row.extend([
+            csv_field(&stats.id),
+            stats.tracked_ms.to_string(),
+            stats.distance.to_string(),
+            stats.average_speed.to_string(),
+            stats.transitions.to_string(),
+            stats.last_active_at.to_string(),
+            stats.idle_ms.to_string(),
+        ]);
+        row.extend(states.iter().map(|state| {
+            stats
+                .state_ms
+                .get(*state)
+                .copied()
+                .unwrap_or_default()
+                .to_string()
+        }));
+        csv.push_str(&row.join(","));
+        csv.push_str("\r\n");
+    }
+    csv
+}
+
+impl LeaderboardJson {
+    pub fn to_csv(&self) -> String {
+        to_csv(
+            self.creatures
+                .iter()
+                .map(|ranked| (Some(ranked.rank), &ranked.stats)),
+        )
+    }
+}
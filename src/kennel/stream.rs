@@ -3,21 +3,41 @@ use std::task::{Context, Poll};
 use pin_project_lite::pin_project;
 use rocket::futures::stream::{Fuse, Stream, StreamExt};
 
+#[derive(Debug, PartialEq)]
+pub enum Merged<A, B> {
+    First(A),
+    Second(B),
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Fairness {
+    // alternate which stream is polled first after every item
+    RoundRobin,
+    // always poll the first stream first
+    Biased,
+}
+
 pin_project! {
     #[derive(Debug)]
-    pub struct GreedyZip<S1: Stream, S2: Stream> {
+    pub struct Merge<S1: Stream, S2: Stream> {
         #[pin]
         stream1: Fuse<S1>,
         #[pin]
         stream2: Fuse<S2>,
+        fairness: Fairness,
+        second_first: bool,
+        is_terminated: bool,
     }
 }
 
-impl<S1: Stream, S2: Stream> GreedyZip<S1, S2> {
-    fn new(stream1: S1, stream2: S2) -> Self {
+impl<S1: Stream, S2: Stream> Merge<S1, S2> {
+    fn new(stream1: S1, stream2: S2, fairness: Fairness) -> Self {
         Self {
             stream1: stream1.fuse(),
             stream2: stream2.fuse(),
+            fairness,
+            second_first: false,
+            is_terminated: false,
         }
     }
 
@@ -26,45 +46,204 @@ impl<S1: Stream, S2: Stream> GreedyZip<S1, S2> {
     }
 }
 
-impl<S1, S2> Stream for GreedyZip<S1, S2>
+impl<S1, S2> Stream for Merge<S1, S2>
 where
     S1: Stream,
     S2: Stream,
 {
-    type Item = (Option<S1::Item>, Option<S2::Item>);
+    type Item = Merged<S1::Item, S2::Item>;
 
     fn poll_next(self: std::pin::Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
         let mut this = self.project();
-        match (
-            this.stream1.as_mut().poll_next(cx),
-            this.stream2.as_mut().poll_next(cx),
-        ) {
-            (Poll::Ready(Some(item1)), Poll::Ready(Some(item2))) => {
-                Poll::Ready(Some((Some(item1), Some(item2))))
+        if *this.is_terminated {
+            return Poll::Ready(None);
+        }
+
+        let second_first = matches!(this.fairness, Fairness::RoundRobin) && *this.second_first;
+        for poll_second in [second_first, !second_first] {
+            let poll = if poll_second {
+                this.stream2
+                    .as_mut()
+                    .poll_next(cx)
+                    .map(|item| item.map(Merged::Second))
+            } else {
+                this.stream1
+                    .as_mut()
+                    .poll_next(cx)
+                    .map(|item| item.map(Merged::First))
+            };
+
+            match poll {
+                Poll::Ready(Some(item)) => {
+                    // the other stream goes first next time
+                    *this.second_first = !poll_second;
+                    return Poll::Ready(Some(item));
+                }
+                // end as soon as either stream ends
+                Poll::Ready(None) => {
+                    *this.is_terminated = true;
+                    return Poll::Ready(None);
+                }
+                Poll::Pending => {}
             }
-            (Poll::Ready(Some(item1)), _) => Poll::Ready(Some((Some(item1), None))),
-            (_, Poll::Ready(Some(item2))) => Poll::Ready(Some((None, Some(item2)))),
-            (_, _) if this.stream1.is_done() && this.stream2.is_done() => Poll::Ready(None),
-            (_, _) => Poll::Pending,
         }
+
+        Poll::Pending
     }
 
     fn size_hint(&self) -> (usize, Option<usize>) {
+        if self.is_terminated {
+            return (0, Some(0));
+        }
+
         let (lower1, upper1) = self.stream1.size_hint();
         let (lower2, upper2) = self.stream2.size_hint();
 
-        let lower = usize::min(lower1, lower2);
+        // every remaining item could still arrive before the end, but the
+        // shorter stream may end before anything else arrives
         let upper = match (upper1, upper2) {
             (Some(u1), Some(u2)) => usize::checked_add(u1, u2),
-            (Some(u1), None) => Some(u1),
-            (None, Some(u2)) => Some(u2),
-            (None, None) => None,
+            _ => None,
         };
 
-        (lower, upper)
+        (usize::min(lower1, lower2), upper)
     }
 }
 
-pub fn greedy_zip<S1: Stream, S2: Stream>(stream1: S1, stream2: S2) -> GreedyZip<S1, S2> {
-    GreedyZip::new(stream1, stream2)
+pub fn merge<S1: Stream, S2: Stream>(
+    stream1: S1,
+    stream2: S2,
+    fairness: Fairness,
+) -> Merge<S1, S2> {
+    Merge::new(stream1, stream2, fairness)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng, rngs::StdRng};
+    use rocket::futures::stream::{self, StreamExt};
+
+    use super::*;
+
+    fn items(len: usize, offset: usize) -> Vec<usize> {
+        (offset..offset + len).collect()
+    }
+
+    #[rocket::async_test]
+    async fn round_robin_alternates_between_ready_streams() {
+        let merged = merge(
+            stream::iter(items(3, 0)),
+            stream::iter(items(3, 10)),
+            Fairness::RoundRobin,
+        )
+        .collect::<Vec<_>>()
+        .await;
+
+        assert_eq!(
+            merged,
+            vec![
+                Merged::First(0),
+                Merged::Second(10),
+                Merged::First(1),
+                Merged::Second(11),
+                Merged::First(2),
+                Merged::Second(12),
+            ]
+        );
+    }
+
+    #[rocket::async_test]
+    async fn biased_drains_the_first_stream_first() {
+        let merged = merge(
+            stream::iter(items(2, 0)).chain(stream::pending()),
+            stream::iter(items(2, 10)),
+            Fairness::Biased,
+        )
+        .collect::<Vec<_>>()
+        .await;
+
+        assert_eq!(
+            merged,
+            vec![
+                Merged::First(0),
+                Merged::First(1),
+                Merged::Second(10),
+                Merged::Second(11),
+            ]
+        );
+    }
+
+    #[rocket::async_test]
+    async fn ends_with_the_first_finished_stream() {
+        let mut merged = merge(
+            stream::iter(items(2, 0)),
+            stream::pending::<usize>(),
+            Fairness::RoundRobin,
+        );
+
+        assert_eq!(merged.next().await, Some(Merged::First(0)));
+        assert_eq!(merged.next().await, Some(Merged::First(1)));
+        assert_eq!(merged.next().await, None);
+        assert_eq!(merged.next().await, None);
+        assert_eq!(merged.size_hint(), (0, Some(0)));
+    }
+
+    // for random lengths and policies, every item arrives in order and
+    // `size_hint` always brackets what is actually left
+    #[rocket::async_test]
+    async fn merge_properties_hold_for_random_streams() {
+        let mut rng = StdRng::seed_from_u64(0x6b656e6e656c);
+
+        for _ in 0..500 {
+            let len1 = rng.random_range(0..20);
+            let len2 = rng.random_range(0..20);
+            let fairness = if rng.random_bool(0.5) {
+                Fairness::RoundRobin
+            } else {
+                Fairness::Biased
+            };
+
+            let mut merged = merge(
+                stream::iter(items(len1, 0)),
+                stream::iter(items(len2, 100)),
+                fairness,
+            );
+
+            let mut hints = vec![merged.size_hint()];
+            let mut sources = Vec::new();
+            let mut firsts = Vec::new();
+            let mut seconds = Vec::new();
+            while let Some(item) = merged.next().await {
+                sources.push(matches!(item, Merged::First(_)));
+                match item {
+                    Merged::First(item) => firsts.push(item),
+                    Merged::Second(item) => seconds.push(item),
+                }
+                hints.push(merged.size_hint());
+            }
+
+            let total = firsts.len() + seconds.len();
+            for (seen, (lower, upper)) in hints.iter().enumerate() {
+                let remaining = total - seen;
+                assert!(*lower <= remaining, "{:?}", fairness);
+                assert!(upper.is_none_or(|upper| remaining <= upper));
+            }
+
+            // items from each stream keep their order
+            assert_eq!(firsts, items(firsts.len(), 0));
+            assert_eq!(seconds, items(seconds.len(), 100));
+
+            // it ends once one of them runs out
+            assert!(firsts.len() == len1 || seconds.len() == len2);
+
+            // with both streams always ready, round robin alternates for as
+            // long as both have items
+            if matches!(fairness, Fairness::RoundRobin) {
+                let shared = usize::min(len1, len2);
+                for (i, is_first) in sources.iter().take(shared * 2).enumerate() {
+                    assert_eq!(*is_first, i % 2 == 0);
+                }
+            }
+        }
+    }
 }
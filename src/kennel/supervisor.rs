@@ -0,0 +1,109 @@
+use std::{any::Any, future::Future, sync::Arc, time::Duration, time::SystemTime};
+
+use rocket::{
+    futures::lock::Mutex,
+    tokio::{self, time::sleep},
+};
+use serde::Serialize;
+
+use crate::{kennel::tick::unix_millis, metrics::Metrics};
+
+static MIN_BACKOFF: Duration = Duration::from_secs(1);
+static MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+#[derive(Serialize, Clone, Default)]
+pub struct SupervisorHealth {
+    restarts: u64,
+    errors: u64,
+    // reset by the next good tick
+    consecutive_failures: u32,
+    last_error: Option<String>,
+    last_error_at: Option<u64>,
+}
+
+impl SupervisorHealth {
+    pub fn is_ok(&self) -> bool {
+        self.consecutive_failures == 0
+    }
+}
+
+// doubles with every failure in a row, up to `MAX_BACKOFF`
+fn backoff(failures: u32) -> Duration {
+    let factor = 2u32.saturating_pow(failures.saturating_sub(1));
+    MIN_BACKOFF.saturating_mul(factor).min(MAX_BACKOFF)
+}
+
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
+
+// keeps a task running through errors and panics, and keeps score
+pub struct Supervisor {
+    name: &'static str,
+    health: Mutex<SupervisorHealth>,
+    metrics: Arc<Metrics>,
+}
+
+impl Supervisor {
+    pub fn new(name: &'static str, metrics: Arc<Metrics>) -> Self {
+        Supervisor {
+            name,
+            health: Mutex::new(SupervisorHealth::default()),
+            metrics,
+        }
+    }
+
+    async fn record_failure(&self, message: String) -> Duration {
+        let mut health = self.health.lock().await;
+        health.consecutive_failures = health.consecutive_failures.saturating_add(1);
+        health.last_error = Some(message);
+        health.last_error_at = Some(unix_millis(SystemTime::now()));
+        backoff(health.consecutive_failures)
+    }
+
+    // an error the task recovers from itself, returns how long to back off
+    pub async fn record_error(&self, context: &str, message: String) -> Duration {
+        log::error!("{} in {}: {}", context, self.name, message);
+        self.metrics.increment("kennel_tick_errors_total");
+        self.health.lock().await.errors += 1;
+        self.record_failure(format!("{}: {}", context, message))
+            .await
+    }
+
+    pub async fn record_success(&self) {
+        self.health.lock().await.consecutive_failures = 0;
+    }
+
+    pub async fn health(&self) -> SupervisorHealth {
+        self.health.lock().await.clone()
+    }
+
+    // runs `task` until it returns, starting it over whenever it panics
+    pub async fn supervise<F, Fut>(&self, mut task: F)
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        loop {
+            let error = match tokio::spawn(task()).await {
+                Ok(()) => break,
+                Err(error) if error.is_panic() => error,
+                Err(_) => break,
+            };
+
+            let message = format!("panicked: {}", panic_message(error.into_panic()));
+            log::error!("Restarting {} after it {}", self.name, message);
+            self.metrics.increment("kennel_tick_restarts_total");
+            self.health.lock().await.restarts += 1;
+
+            let backoff = self.record_failure(message).await;
+            sleep(backoff).await;
+        }
+    }
+}
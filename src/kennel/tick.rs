@@ -0,0 +1,64 @@
+use std::{
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use kennel_club::Kennel;
+
+use crate::kennel::json::TickJson;
+
+pub static NOTHING_TO_DRAW: &str = "There is no kennel to draw while replaying a recording";
+
+// where the creatures of a tick came from
+#[derive(Clone)]
+pub enum Source {
+    Kennel(Arc<Kennel>),
+    // a tick of a replayed recording, which only kept the JSON
+    Recorded(Arc<TickJson>),
+}
+
+impl From<Kennel> for Source {
+    fn from(kennel: Kennel) -> Self {
+        Source::Kennel(Arc::new(kennel))
+    }
+}
+
+// a kennel state along with when it was produced
+#[derive(Clone)]
+pub struct Tick {
+    pub number: u64,
+    pub timestamp: SystemTime,
+    pub source: Source,
+}
+
+impl Tick {
+    pub fn first(source: impl Into<Source>) -> Self {
+        Tick {
+            number: 0,
+            timestamp: SystemTime::now(),
+            source: source.into(),
+        }
+    }
+
+    pub fn next(&self, source: impl Into<Source>) -> Self {
+        Tick {
+            number: self.number + 1,
+            timestamp: SystemTime::now(),
+            source: source.into(),
+        }
+    }
+
+    // `None` while replaying, there's nothing to draw
+    pub fn kennel(&self) -> Option<&Arc<Kennel>> {
+        match &self.source {
+            Source::Kennel(kennel) => Some(kennel),
+            Source::Recorded(_) => None,
+        }
+    }
+}
+
+pub fn unix_millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
@@ -0,0 +1,195 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use kennel_club::{ImageFormat, Kennel};
+use serde::Serialize;
+
+use crate::kennel::{animate::encode_gif, config::Config, files::write_atomic, tick::unix_millis};
+
+static TIMELAPSE_EXTENSION: &str = "gif";
+
+// `YYYY-MM-DD` of `time` in UTC, days roll over at midnight UTC
+pub fn utc_date(time: SystemTime) -> String {
+    let days = time
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() / 86400)
+        .unwrap_or_default();
+
+    // civil date from days since the epoch, see
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719468;
+    let era = z / 146097;
+    let doe = z % 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as u64;
+
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+// also keeps request paths from reaching outside the archive
+fn is_date(value: &str) -> bool {
+    let bytes = value.as_bytes();
+    bytes.len() == 10
+        && bytes.iter().enumerate().all(|(i, byte)| match i {
+            4 | 7 => *byte == b'-',
+            _ => byte.is_ascii_digit(),
+        })
+}
+
+#[derive(Serialize)]
+pub struct TimelapseJson {
+    date: String,
+    url: String,
+    bytes: u64,
+}
+
+// frames sampled through the day under `frames/<date>`, each finished day
+// compiled into `<date>.gif`
+pub struct Timelapse {
+    dir: PathBuf,
+    size: u32,
+    frame_delay_ms: u64,
+    keep_days: usize,
+}
+
+impl Timelapse {
+    pub fn new(config: &Config) -> Self {
+        Timelapse {
+            dir: config.timelapse_dir.clone(),
+            size: config.timelapse_size.max(1),
+            frame_delay_ms: config.timelapse_frame_delay_ms,
+            keep_days: config.timelapse_keep_days.max(1),
+        }
+    }
+
+    fn frames_dir(&self) -> PathBuf {
+        self.dir.join("frames")
+    }
+
+    pub fn path(&self, date: &str) -> Option<PathBuf> {
+        if !is_date(date) {
+            return None;
+        }
+        let path = self.dir.join(format!("{}.{}", date, TIMELAPSE_EXTENSION));
+        path.is_file().then_some(path)
+    }
+
+    // newest first
+    pub fn index(&self) -> Vec<TimelapseJson> {
+        let mut days = fs::read_dir(&self.dir)
+            .map(|entries| {
+                entries
+                    .flatten()
+                    .filter_map(|entry| {
+                        let path = entry.path();
+                        let date = path.file_stem()?.to_str()?.to_string();
+                        let is_timelapse = is_date(&date)
+                            && path.extension().is_some_and(|e| e == TIMELAPSE_EXTENSION);
+                        is_timelapse.then(|| TimelapseJson {
+                            url: format!("/api/kennel-club/timelapse/{}", date),
+                            bytes: entry.metadata().map(|m| m.len()).unwrap_or_default(),
+                            date,
+                        })
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        days.sort_by(|a, b| b.date.cmp(&a.date));
+        days
+    }
+
+    // blocking, renders straight at the time-lapse size
+    pub fn sample(&self, kennel: &Kennel, now: SystemTime) -> Result<(), String> {
+        let data = kennel.get_image(self.size, self.size, ImageFormat::Png)?;
+        let dir = self.frames_dir().join(utc_date(now));
+        fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+        fs::write(dir.join(format!("{:020}.png", unix_millis(now))), data)
+            .map_err(|e| e.to_string())
+    }
+
+    // blocking, compiles every day before `today` that still has frames,
+    // including ones missed while the server was down
+    pub fn compile_pending(&self, today: &str) {
+        let Ok(entries) = fs::read_dir(self.frames_dir()) else {
+            return;
+        };
+
+        let mut compiled = false;
+        for entry in entries.flatten() {
+            let Some(date) = entry.file_name().to_str().map(str::to_string) else {
+                continue;
+            };
+            if !is_date(&date) || date.as_str() >= today {
+                continue;
+            }
+
+            // frames stay around for another try unless the day compiled
+            match self.compile(&date, &entry.path()) {
+                Ok(Some(path)) => {
+                    log::info!("Compiled kennel time-lapse {}", path.display());
+                    compiled = true;
+                }
+                Ok(None) => {}
+                Err(message) => {
+                    log::warn!("Error compiling time-lapse for {}: {}", date, message);
+                    continue;
+                }
+            }
+            if let Err(e) = fs::remove_dir_all(entry.path()) {
+                log::warn!("Error removing time-lapse frames for {}: {}", date, e);
+            }
+        }
+
+        if compiled {
+            self.prune();
+        }
+    }
+
+    // `None` for a day without any frames, there's nothing to keep
+    fn compile(&self, date: &str, frames_dir: &Path) -> Result<Option<PathBuf>, String> {
+        let mut frames = fs::read_dir(frames_dir)
+            .map_err(|e| e.to_string())?
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|e| e == "png"))
+            .collect::<Vec<_>>();
+        frames.sort();
+        if frames.is_empty() {
+            return Ok(None);
+        }
+
+        // decoded one at a time as the encoder gets to them
+        let delays = vec![self.frame_delay_ms; frames.len()];
+        let images = frames.iter().filter_map(|path| match image::open(path) {
+            Ok(image) => Some(image.to_rgba8()),
+            Err(e) => {
+                log::warn!("Skipping time-lapse frame {}: {}", path.display(), e);
+                None
+            }
+        });
+        let data = encode_gif(images, &delays)?;
+
+        let path = self.dir.join(format!("{}.{}", date, TIMELAPSE_EXTENSION));
+        write_atomic(&path, &data)?;
+        Ok(Some(path))
+    }
+
+    // keeps the newest `keep_days` time-lapses
+    fn prune(&self) {
+        for stale in self.index().into_iter().skip(self.keep_days) {
+            let Some(path) = self.path(&stale.date) else {
+                continue;
+            };
+            if let Err(e) = fs::remove_file(&path) {
+                log::warn!("Error removing time-lapse {}: {}", path.display(), e);
+            }
+        }
+    }
+}
@@ -1,18 +1,29 @@
+mod auth;
 mod cache;
+mod connections;
 mod cors;
+mod health;
 mod kennel;
+mod metrics;
 mod twitch;
 
+use auth::{Auth, AuthConfig};
 use cache::Cache;
+use connections::{ConnectionLimiter, ConnectionLimits, ConnectionPermit};
 use cors::Cors;
+use health::health_handler;
+use metrics::{Metrics, metrics_handler};
 use rocket::fs::{FileServer, NamedFile};
 use rocket::futures::{SinkExt, StreamExt};
 use rocket::{catch, catchers, get, routes};
 use std::path::Path;
+use std::sync::Arc;
 use twitch::twitch_handler;
 use ws::Message;
 
-use crate::kennel::{init_kennel, kennel_routes, ws_kennel_routes};
+use crate::kennel::{
+    admin_routes, init_kennel, kennel_routes, unavailable_catcher, ws_kennel_routes,
+};
 
 #[catch(404)]
 async fn not_found() -> Option<NamedFile> {
@@ -27,9 +38,10 @@ fn ping_handler() -> &'static str {
 }
 
 #[get("/ping")]
-fn ws_ping_handler(ws: ws::WebSocket) -> ws::Channel<'static> {
+fn ws_ping_handler(ws: ws::WebSocket, permit: ConnectionPermit) -> ws::Channel<'static> {
     ws.channel(move |mut stream| {
         Box::pin(async move {
+            let _permit = permit;
             while let Some(message) = stream.next().await {
                 match message {
                     Ok(Message::Close(_)) | Err(_) => break,
@@ -45,16 +57,36 @@ fn ws_ping_handler(ws: ws::WebSocket) -> ws::Channel<'static> {
 
 #[rocket::main]
 async fn main() -> Result<(), String> {
-    let (kennel, kennel_cleanup) = init_kennel();
+    let metrics = Arc::new(Metrics::default());
+    let (kennel, kennel_cleanup) = init_kennel(metrics.clone());
+    let limits = ConnectionLimits::from_figment(&rocket::Config::figment())?;
+    let connections = Arc::new(ConnectionLimiter::new(limits, metrics.clone()));
+    let auth_config = AuthConfig::from_figment(&rocket::Config::figment())?;
+    let auth = Arc::new(Auth::load(&auth_config, metrics.clone())?);
     let _server = rocket::build()
         .mount("/api/kennel-club", kennel_routes())
-        .mount("/api", routes![ping_handler, twitch_handler,])
+        .mount(
+            "/api",
+            routes![
+                ping_handler,
+                twitch_handler,
+                health_handler,
+                metrics_handler,
+            ],
+        )
+        .mount("/api/admin/kennel", admin_routes())
         .mount("/ws/kennel-club", ws_kennel_routes())
         .mount("/ws", routes![ws_ping_handler])
         .mount("/", FileServer::from("./static"))
         .register("/", catchers![not_found])
+        .register("/api/kennel-club", catchers![unavailable_catcher])
+        .register("/api/admin/kennel", catchers![unavailable_catcher])
+        .register("/ws/kennel-club", catchers![unavailable_catcher])
         .manage(Cache::<String, String>::default())
         .manage(kennel)
+        .manage(metrics)
+        .manage(connections)
+        .manage(auth)
         .attach(kennel_cleanup)
         .attach(Cors)
         .launch()
@@ -0,0 +1,53 @@
+use std::{collections::BTreeMap, fmt::Write, sync::Arc, sync::Mutex};
+
+use rocket::{State, get, http::ContentType};
+
+use crate::auth::MetricsReader;
+
+#[derive(Default)]
+pub struct Metrics {
+    counters: Mutex<BTreeMap<&'static str, u64>>,
+    gauges: Mutex<BTreeMap<&'static str, i64>>,
+}
+
+impl Metrics {
+    pub fn increment(&self, name: &'static str) {
+        self.add(name, 1);
+    }
+
+    pub fn add(&self, name: &'static str, value: u64) {
+        let mut counters = self.counters.lock().expect("Lock metrics counters");
+        *counters.entry(name).or_default() += value;
+    }
+
+    pub fn set_gauge(&self, name: &'static str, value: i64) {
+        let mut gauges = self.gauges.lock().expect("Lock metrics gauges");
+        gauges.insert(name, value);
+    }
+
+    // prometheus text exposition format
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+
+        let counters = self.counters.lock().expect("Lock metrics counters");
+        for (name, value) in counters.iter() {
+            let _ = writeln!(out, "# TYPE {} counter\n{} {}", name, name, value);
+        }
+        drop(counters);
+
+        let gauges = self.gauges.lock().expect("Lock metrics gauges");
+        for (name, value) in gauges.iter() {
+            let _ = writeln!(out, "# TYPE {} gauge\n{} {}", name, name, value);
+        }
+
+        out
+    }
+}
+
+#[get("/metrics")]
+pub fn metrics_handler(
+    _reader: MetricsReader,
+    metrics: &State<Arc<Metrics>>,
+) -> (ContentType, String) {
+    (ContentType::Plain, metrics.render())
+}